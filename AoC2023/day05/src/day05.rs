@@ -1,16 +1,102 @@
 use std::{
     cmp::{max, min},
+    error::Error,
+    fmt::{Debug, Display},
+    marker::PhantomData,
     num::ParseIntError,
-    ops::Range,
+    ops::{AddAssign, Range},
+    path::Path,
     str::FromStr,
 };
 
 use itertools::Itertools;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use affine::AffineMapping;
+use category::{parse_header, CategoryGraph};
+use diagram::Diagram;
+use provenance::TracedRangeMap;
+use snapshot::Snapshot;
+
+mod affine;
+mod brute_force;
+mod category;
+mod diagram;
+mod provenance;
+mod snapshot;
+
+/// Primitive integers that can be used as ids of a [RangeMap]
+///
+/// `Len` is the unsigned integer of the same width, it can hold the distance
+/// between any two values of `Self` (even for signed types) which means
+/// offsets never have to be computed as a possibly negative `dest - src`
+trait RangeId:
+    Copy
+    + Ord
+    + Debug
+    + Display
+    + FromStr<Err = ParseIntError>
+    + Send
+    + Sync
+    + Serialize
+    + DeserializeOwned
+{
+    type Len: Copy
+        + Ord
+        + Debug
+        + Display
+        + AddAssign
+        + From<u32>
+        + FromStr<Err = ParseIntError>
+        + Send
+        + Sync
+        + Serialize
+        + DeserializeOwned;
+
+    const MIN: Self;
+    const MAX: Self;
+    const LEN_ZERO: Self::Len;
+
+    /// `self + len`, [None] on overflow
+    fn checked_add_len(self, len: Self::Len) -> Option<Self>;
+    /// `end - self`, `end` must not be smaller than `self`
+    fn len_to(self, end: Self) -> Self::Len;
+
+    /// (lossy) position for drawing, see [diagram]
+    fn as_f64(self) -> f64;
+}
+
+macro_rules! impl_range_id {
+    ($id:ty, $len:ty, $checked_add:ident) => {
+        impl RangeId for $id {
+            type Len = $len;
+
+            const MIN: Self = <$id>::MIN;
+            const MAX: Self = <$id>::MAX;
+            const LEN_ZERO: Self::Len = 0;
+
+            fn checked_add_len(self, len: Self::Len) -> Option<Self> {
+                self.$checked_add(len)
+            }
+            fn len_to(self, end: Self) -> Self::Len {
+                debug_assert!(end >= self);
+                end.abs_diff(self)
+            }
+            fn as_f64(self) -> f64 {
+                self as f64
+            }
+        }
+    };
+}
+impl_range_id!(u32, u32, checked_add);
+impl_range_id!(u64, u64, checked_add);
+impl_range_id!(i64, u64, checked_add_unsigned);
+impl_range_id!(u128, u128, checked_add);
 
 type Id = i64;
-type SeedRange = Range<Id>;
+type SeedRange<T> = Range<T>;
 
-fn parse_seed_ranges<'a>(section: &str) -> Result<Vec<SeedRange>, String> {
+fn parse_seed_ranges<T: RangeId>(section: &str) -> Result<Vec<SeedRange<T>>, String> {
     let mut items = section.trim_start_matches("seeds: ").split_whitespace();
 
     let mut result = Vec::with_capacity(items.size_hint().0 / 2);
@@ -20,56 +106,313 @@ fn parse_seed_ranges<'a>(section: &str) -> Result<Vec<SeedRange>, String> {
             "uneven amount of items, expected one more following '{item}'"
         ))?;
 
-        let range_start = item.parse::<Id>().map_err(|e| e.to_string())?;
-        let range_len = other_item.parse::<Id>().map_err(|e| e.to_string())?;
-        result.push(range_start..(range_start + range_len));
+        let range_start = item.parse::<T>().map_err(|e| e.to_string())?;
+        let range_len = other_item.parse::<T::Len>().map_err(|e| e.to_string())?;
+        let range_end = range_start
+            .checked_add_len(range_len)
+            .ok_or(format!("seed range '{item} {other_item}' overflows"))?;
+        result.push(range_start..range_end);
     }
 
     Ok(result)
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-struct RangeMapping {
-    src: Id,
-    dest: Id,
-    len: Id,
+/// every item is a single seed (part 1)
+fn parse_seeds<T: RangeId>(section: &str) -> Result<Vec<T>, String> {
+    section
+        .trim_start_matches("seeds: ")
+        .split_whitespace()
+        .map(|item| item.parse::<T>().map_err(|e| e.to_string()))
+        .collect()
 }
-impl RangeMapping {
-    fn from_len(src: Id, dest: Id, len: Id) -> Self {
-        debug_assert!(len > 0);
-        Self { src, dest, len }
+
+/// A set of ids, stored as sorted, disjoint and non-adjacent ranges
+/// (i.e. normalized, two sets containing the same ids are equal)
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct RangeSet<T: RangeId>(Vec<Range<T>>);
+
+impl<T: RangeId> RangeSet<T> {
+    /// normalizes possibly empty, unsorted, overlapping or adjacent ranges
+    fn new(ranges: impl IntoIterator<Item = Range<T>>) -> Self {
+        let mut result: Vec<Range<T>> = vec![];
+        for range in ranges
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .sorted_by_key(|range| range.start)
+        {
+            match result.last_mut() {
+                Some(last) if range.start <= last.end => last.end = max(last.end, range.end),
+                _ => result.push(range),
+            }
+        }
+        Self(result)
     }
 
-    /// gaps are mapped to themselves (default)
-    fn dflt_from_bounds(src: Id, src_end_excl: Id) -> Self {
-        // debug_assert!(src_end_excl > src);
-        if src_end_excl <= src {
-            dbg!(src);
-            dbg!(src_end_excl);
-            panic!("invalid range");
+    fn ranges(&self) -> &[Range<T>] {
+        &self.0
+    }
+
+    /// the lowest id in the set
+    fn min(&self) -> Option<T> {
+        self.0.first().map(|range| range.start)
+    }
+
+    fn contains(&self, id: T) -> bool {
+        let idx = self.0.partition_point(|range| range.end <= id);
+        self.0.get(idx).is_some_and(|range| range.start <= id)
+    }
+
+    fn union(&self, other: &Self) -> Self {
+        Self::new(self.0.iter().chain(&other.0).cloned())
+    }
+
+    fn intersection(&self, other: &Self) -> Self {
+        let mut result = vec![];
+        let (mut left, mut right) = (self.0.iter().peekable(), other.0.iter().peekable());
+        while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+            let start = max(l.start, r.start);
+            let end = min(l.end, r.end);
+            if start < end {
+                result.push(start..end);
+            }
+            // the range ending first can't overlap anything further
+            if l.end <= r.end {
+                left.next();
+            } else {
+                right.next();
+            }
         }
-        Self {
-            src,
-            dest: src,
-            len: src_end_excl - src,
+        Self(result)
+    }
+
+    /// every id of `domain` that is not part of `self`
+    fn complement(&self, domain: Domain<T>) -> Self {
+        let mut result = vec![];
+        let mut start = domain.start;
+        for range in &self.0 {
+            if range.start > start {
+                result.push(start..min(range.start, domain.end_excl));
+            }
+            start = max(start, range.end);
+        }
+        if start < domain.end_excl {
+            result.push(start..domain.end_excl);
+        }
+        Self::new(result)
+    }
+
+    fn difference(&self, other: &Self) -> Self {
+        self.intersection(&other.complement(Domain::full()))
+    }
+}
+
+impl<T: RangeId> Default for RangeSet<T> {
+    fn default() -> Self {
+        Self(vec![])
+    }
+}
+
+impl<T: RangeId> FromIterator<Range<T>> for RangeSet<T> {
+    fn from_iter<I: IntoIterator<Item = Range<T>>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+/// The ids a total [RangeMap] covers: `start..end_excl`
+///
+/// [RangeId::MAX] itself can't be part of any (half-open) range, which makes
+/// it the largest possible `end_excl`, see [Domain::full]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Domain<T: RangeId> {
+    start: T,
+    end_excl: T,
+}
+
+impl<T: RangeId> Domain<T> {
+    fn new(start: T, end_excl: T) -> Result<Self, RangeError<T>> {
+        if end_excl <= start {
+            return Err(RangeError::Empty { start, end_excl });
         }
+        Ok(Self { start, end_excl })
+    }
+
+    /// every id of `T` (except for [RangeId::MAX]), including negative ones
+    fn full() -> Self {
+        Self::new(T::MIN, T::MAX).expect("every id type has more than one value")
+    }
+
+    fn contains(&self, id: T) -> bool {
+        self.start <= id && id < self.end_excl
+    }
+}
+
+impl<T: RangeId> Default for Domain<T> {
+    fn default() -> Self {
+        Self::full()
     }
-    /// default ([Self::dflt]) for the upper range (up to [Id::MAX])
-    fn upper_dflt(src: Id) -> Self {
-        Self::dflt_from_bounds(src, Id::MAX)
+}
+
+impl<T: RangeId> Display for Domain<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end_excl)
     }
+}
+
+/// Why a [Piece] (or a [RangeMap] made of them) can't be built
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum RangeError<T: RangeId> {
+    /// the range would not contain a single value
+    Empty { start: T, end_excl: T },
+    /// `start + len` does not fit into the id type
+    Overflow { start: T, len: T::Len },
+    /// the `src` range of a piece or the ids it is mapped to exceed the [Domain]
+    /// of its map
+    OutOfDomain {
+        src: T,
+        src_end_excl: T,
+        domain: Domain<T>,
+    },
+    /// a `src` range starts before the previous one ended
+    Overlap { start: T, previous_end_excl: T },
+    /// the `dest` range of a scaled mapping (see [affine::AffineMapping]), from `dest`
+    /// in steps of `scale` for `len` values, does not fit into the id type
+    ScaledOverflow { dest: T, len: T::Len, scale: i128 },
+    /// a single id outside the [Domain] of a total map, always the case for
+    /// [RangeId::MAX]
+    Unmapped { id: T },
+}
+
+impl<T: RangeId> Display for RangeError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RangeError::Empty { start, end_excl } => write!(f, "empty range {start}..{end_excl}"),
+            RangeError::Overflow { start, len } => {
+                write!(f, "range of length {len} starting at {start} overflows")
+            }
+            RangeError::OutOfDomain {
+                src,
+                src_end_excl,
+                domain,
+            } => write!(
+                f,
+                "mapping of {src}..{src_end_excl} exceeds the domain {domain}"
+            ),
+            RangeError::Overlap {
+                start,
+                previous_end_excl,
+            } => write!(
+                f,
+                "range starting at {start} overlaps the previous one ending at {previous_end_excl}"
+            ),
+            RangeError::ScaledOverflow { dest, len, scale } => write!(
+                f,
+                "dest range of length {len} starting at {dest} with scale {scale} overflows"
+            ),
+            RangeError::Unmapped { id } => write!(f, "{id} is outside the domain of the map"),
+        }
+    }
+}
+
+impl<T: RangeId> Error for RangeError<T> {}
+
+/// What a [RangeMap] is made of: a `src` range and where its ids are mapped to
+///
+/// [RangeMapping]s shift their range by an offset, which keeps the image of a
+/// range a range (see [RangeMap::image]), [AffineMapping]s scale it as well
+/// Both are closed under [Piece::merge_with_overlap], so gap filling, joins and
+/// lookups are shared and offsets stay the fast special case
+trait Piece<T: RangeId>:
+    Copy + Debug + Eq + Send + Sync + Serialize + DeserializeOwned + FromStr<Err = String>
+{
+    fn src(&self) -> T;
 
     /// upper bounds is exclusive
-    fn src_end_excl(&self) -> Id {
-        self.src + self.len
+    fn src_end_excl(&self) -> T;
+
+    /// where `id` (which has to be part of the `src` range) is mapped to
+    fn map_point(&self, id: T) -> T;
+
+    /// gaps are mapped to themselves (default)
+    fn dflt_from_bounds(src: T, src_end_excl: T) -> Result<Self, RangeError<T>>;
+
+    /// the part of `self` whose `src` lies within `start..end_excl`
+    /// [None] if there is no such part
+    fn restrict_src(&self, start: T, end_excl: T) -> Option<Self>;
+
+    /// Creates a new piece based on the overlap between where `self` maps to
+    /// and `other`'s src, mapping like `other(self(x))`
+    /// The new instance will have a (sub)range of `self.src` as src
+    /// [None] if `self` maps nothing into `other`'s src
+    fn merge_with_overlap(&self, other: &Self) -> Option<Self>;
+
+    /// `self` and `next` as a single piece, if `next` starts where `self` ends
+    /// and continues its mapping
+    fn extend(&self, next: &Self) -> Option<Self>;
+
+    /// whether the `src` range and every id it is mapped to lie within `domain`
+    fn fits_into(&self, domain: Domain<T>) -> bool;
+
+    /// the lowest id the part of `self` with `src` in `range` is mapped to
+    fn lowest_dest(&self, range: &Range<T>) -> Option<T>;
+
+    /// the same piece as a [RangeMapping], [None] if it does more than add an offset
+    fn as_offset(&self) -> Option<RangeMapping<T>>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct RangeMapping<T: RangeId> {
+    src: T,
+    dest: T,
+    len: T::Len,
+}
+impl<T: RangeId> RangeMapping<T> {
+    /// both `src..src+len` and `dest..dest+len` have to be non-empty
+    /// and representable by `T`, which is why the end accessors
+    /// ([Piece::src_end_excl], [Self::dest_end_excl]) can never overflow
+    fn from_len(src: T, dest: T, len: T::Len) -> Result<Self, RangeError<T>> {
+        for start in [src, dest] {
+            if start.checked_add_len(len).is_none() {
+                return Err(RangeError::Overflow { start, len });
+            }
+        }
+        if len == T::LEN_ZERO {
+            return Err(RangeError::Empty {
+                start: src,
+                end_excl: src,
+            });
+        }
+        Ok(Self { src, dest, len })
     }
+
     /// upper bounds is exclusive
-    fn dest_end_excl(&self) -> Id {
-        self.dest + self.len
+    fn dest_end_excl(&self) -> T {
+        self.dest
+            .checked_add_len(self.len)
+            .expect("constructor guarantees dest range to fit the id type")
     }
 
-    /// see excalidraw
-    fn self_dest_overlaps_other_src(&self, other: &Self) -> Option<RangeOverlap> {
+    /// the part of `self` whose `dest` lies within `start..end_excl`
+    /// [None] if there is no such part
+    fn restrict_dest(&self, start: T, end_excl: T) -> Option<Self> {
+        let start = max(start, self.dest);
+        let end_excl = min(end_excl, self.dest_end_excl());
+        (start < end_excl).then(|| {
+            let offset = self.dest.len_to(start);
+            Self {
+                src: self
+                    .src
+                    .checked_add_len(offset)
+                    .expect("dest and src ranges have the same length"),
+                dest: start,
+                len: start.len_to(end_excl),
+            }
+        })
+    }
+
+    /// see excalidraw (`doc/aoc2023_day05.excalidraw`, real data can be drawn with [Diagram])
+    fn self_dest_overlaps_other_src(&self, other: &Self) -> Option<RangeOverlap<T>> {
         // 0,5 : 3
         // 6,12 : 1
         // -> 6 : 1
@@ -90,56 +433,105 @@ impl RangeMapping {
         let start = max(self.dest, other.src);
         let end = min(self.dest_end_excl(), other.src_end_excl());
 
-        let len = end - start;
-
-        if len > 0 {
-            Some(RangeOverlap { start, len })
+        if end > start {
+            Some(RangeOverlap {
+                start,
+                len: start.len_to(end),
+            })
         } else {
             None
         }
     }
+}
+
+impl<T: RangeId> Piece<T> for RangeMapping<T> {
+    fn src(&self) -> T {
+        self.src
+    }
+
+    fn src_end_excl(&self) -> T {
+        self.src
+            .checked_add_len(self.len)
+            .expect("constructor guarantees src range to fit the id type")
+    }
+
+    fn map_point(&self, id: T) -> T {
+        self.dest
+            .checked_add_len(self.src.len_to(id))
+            .expect("id should be part of the src range")
+    }
+
+    fn dflt_from_bounds(src: T, src_end_excl: T) -> Result<Self, RangeError<T>> {
+        if src_end_excl <= src {
+            return Err(RangeError::Empty {
+                start: src,
+                end_excl: src_end_excl,
+            });
+        }
+        Self::from_len(src, src, src.len_to(src_end_excl))
+    }
+
+    fn restrict_src(&self, start: T, end_excl: T) -> Option<Self> {
+        let start = max(start, self.src);
+        let end_excl = min(end_excl, self.src_end_excl());
+        (start < end_excl).then(|| Self {
+            src: start,
+            dest: self.map_point(start),
+            len: start.len_to(end_excl),
+        })
+    }
 
-    /// Creates a new [Self] based on the overlap between `self`'s dest
-    /// and `other`'s src
-    /// The new instance will have a (sub)range of `self.src` as src
-    /// and a (sub)range of `other.dest` as dest
     /// Parameters:
     /// - [self]: provides `dest` for the overlap
     /// - [other]: provides `src` for the overlap
     fn merge_with_overlap(&self, other: &Self) -> Option<Self> {
-        if let Some(RangeOverlap {
-            start: overlap_start,
-            len: overlap_len,
-        }) = self.self_dest_overlaps_other_src(other)
-        {
-            let self_src_to_dest_offset = self.dest - self.src;
-            let other_dest_to_src_offset = other.src - other.dest;
-            let overlap_start_in_self_src = overlap_start - self_src_to_dest_offset;
-            let overlap_start_in_other_dest = overlap_start - other_dest_to_src_offset;
-
-            if overlap_start_in_self_src < 0 || overlap_start_in_other_dest < 0 {
-                dbg!(self);
-                dbg!(other);
-                dbg!(overlap_start);
-                dbg!(overlap_len);
-                dbg!(overlap_start_in_self_src);
-                dbg!(overlap_start_in_other_dest);
-                panic!("invalid merged range");
-            }
-
-            Some(Self {
-                src: overlap_start_in_self_src,
-                dest: overlap_start_in_other_dest,
+        // the overlap starts at or after both `self.dest` and `other.src`
+        // so the positions can be found by (unsigned) distances from there
+        self.self_dest_overlaps_other_src(other).map(
+            |RangeOverlap {
+                 start: overlap_start,
+                 len: overlap_len,
+             }| Self {
+                src: self
+                    .src
+                    .checked_add_len(self.dest.len_to(overlap_start))
+                    .expect("overlap is part of self's range"),
+                dest: other.map_point(overlap_start),
                 len: overlap_len,
-            })
-        } else {
-            None
-        }
+            },
+        )
+    }
+
+    /// contiguous in `src` as well as in `dest`, i.e. the same `dest - src` offset
+    fn extend(&self, next: &Self) -> Option<Self> {
+        (self.src_end_excl() == next.src && self.dest_end_excl() == next.dest).then(|| {
+            let mut extended = *self;
+            extended.len += next.len;
+            extended
+        })
+    }
+
+    fn fits_into(&self, domain: Domain<T>) -> bool {
+        [self.src, self.dest]
+            .into_iter()
+            .all(|start| start >= domain.start)
+            && [self.src_end_excl(), self.dest_end_excl()]
+                .into_iter()
+                .all(|end_excl| end_excl <= domain.end_excl)
+    }
+
+    fn lowest_dest(&self, range: &Range<T>) -> Option<T> {
+        self.restrict_src(range.start, range.end)
+            .map(|mapping| mapping.dest)
+    }
+
+    fn as_offset(&self) -> Option<RangeMapping<T>> {
+        Some(*self)
     }
 }
 
-impl PartialEq<(Range<Id>, Range<Id>)> for RangeMapping {
-    fn eq(&self, other: &(Range<Id>, Range<Id>)) -> bool {
+impl<T: RangeId> PartialEq<(Range<T>, Range<T>)> for RangeMapping<T> {
+    fn eq(&self, other: &(Range<T>, Range<T>)) -> bool {
         self.src == other.0.start
             && self.dest == other.1.start
             && self.src_end_excl() == other.0.end
@@ -147,84 +539,158 @@ impl PartialEq<(Range<Id>, Range<Id>)> for RangeMapping {
     }
 }
 
-impl FromStr for RangeMapping {
+impl<T: RangeId> FromStr for RangeMapping<T> {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut values = s.split_whitespace();
-
-        match values
-            .try_fold(vec![], |mut acc, item| {
-                acc.push(item.parse()?);
-                Ok::<Vec<Id>, ParseIntError>(acc)
-            })
-            .as_ref()
-            .map(|values| &values[..])
-        {
-            Ok([first, second, third]) => Ok(Self {
-                dest: *first,
-                src: *second,
-                len: *third,
-            }),
-            Ok(_) => Err(format!("expected 3 uints but got '{}'", s)),
-            Err(e) => Err(e.to_string()),
+        match s.split_whitespace().collect_vec()[..] {
+            [dest, src, len] => {
+                let parse_err = |e: ParseIntError| e.to_string();
+                Self::from_len(
+                    src.parse().map_err(parse_err)?,
+                    dest.parse().map_err(parse_err)?,
+                    len.parse().map_err(parse_err)?,
+                )
+                .map_err(|e| format!("invalid range '{s}': {e}"))
+            }
+            _ => Err(format!("expected 3 uints but got '{}'", s)),
         }
     }
 }
 
+/// Marker for [RangeMap]s that map every value of the [Domain] they were built
+/// on exactly once, see [RangeMap::from_fill_gaps]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Total;
+
+/// Marker for [RangeMap]s that might leave out values (or, after a
+/// [RangeMap::left_join] with overlapping `src` ranges, map them more than once)
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+struct Partial;
+
 /// ASSUMES vec to be sorted by input range start
 /// format: `[(in_range, out_range)]`
 ///
-/// **!!ONLY!!** for instances created using [RangeMap::from_fill_gaps]:
-/// All values in the range `0..`[Id::MAX] are mapped in this structure
-/// see [RangeMap::from_fill_gaps]
-#[derive(PartialEq, Eq, Debug)]
-struct RangeMap(Vec<RangeMapping>);
-
-impl RangeMap {
-    fn from_fill_gaps(value: impl Iterator<Item = RangeMapping>) -> Self {
-        let value = value.sorted_by_key(|mapping| mapping.src);
+/// `C` ([Total] or [Partial]) tracks whether all values of a [Domain] are mapped,
+/// operations relying on it (like [RangeMap::get] and [RangeMap::compose])
+/// only exist on [Total] maps
+/// `P` is the kind of [Piece], plain offsets unless the almanac scales
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(bound = "", transparent)]
+struct RangeMap<T: RangeId, C = Total, P: Piece<T> = RangeMapping<T>>(
+    Vec<P>,
+    #[serde(skip)] PhantomData<(T, C)>,
+);
+
+impl<T: RangeId, P: Piece<T>> RangeMap<T, Total, P> {
+    /// Gaps between the mappings (and towards both ends of `domain`) are
+    /// mapped to themselves, making the map total on `domain`
+    /// fails with [RangeError::Overlap] if any two `src` ranges overlap
+    /// and [RangeError::OutOfDomain] if a mapping doesn't fit into `domain`
+    fn from_fill_gaps(
+        value: impl Iterator<Item = P>,
+        domain: Domain<T>,
+    ) -> Result<Self, RangeError<T>> {
+        let value = value.sorted_by_key(|mapping| mapping.src());
         // *4 guesstimate pulled straight out of my ass
         let mut result = Vec::with_capacity(value.len() * 4);
 
         // fill gaps
-        let mut start = 0;
+        let mut start = domain.start;
         for mapping in value {
-            if mapping.src != start {
+            if !mapping.fits_into(domain) {
+                return Err(RangeError::OutOfDomain {
+                    src: mapping.src(),
+                    src_end_excl: mapping.src_end_excl(),
+                    domain,
+                });
+            }
+            if mapping.src() < start {
+                return Err(RangeError::Overlap {
+                    start: mapping.src(),
+                    previous_end_excl: start,
+                });
+            }
+            if mapping.src() != start {
                 // gaps are mapped to themselves
-                result.push(RangeMapping::dflt_from_bounds(start, mapping.src));
+                result.push(P::dflt_from_bounds(start, mapping.src())?);
             }
             start = mapping.src_end_excl();
             result.push(mapping);
         }
 
-        if start != Id::MAX {
+        if start != domain.end_excl {
             // also fill the gap "above" the defined ranges
-            result.push(RangeMapping::upper_dflt(start));
+            result.push(P::dflt_from_bounds(start, domain.end_excl)?);
         }
 
         result.shrink_to_fit();
-        Self(result)
+        Ok(Self(result, PhantomData))
     }
 
-    /// seeds can be represented as a [RangeMap] that maps the
-    /// seed-ranges to themselves
-    fn from_seeds(seed_ranges: impl Iterator<Item = SeedRange>) -> Self {
-        Self(
-            seed_ranges
-                .into_iter()
-                .sorted_by_key(|e| e.start)
-                .map(|e| RangeMapping::dflt_from_bounds(e.start, e.end))
-                .collect(),
-        )
+    /// The map that sends every value of `domain` to itself, the neutral element
+    /// of [RangeMap::compose]
+    /// Like every total map it leaves out [RangeId::MAX], see [RangeMap::get]
+    fn identity(domain: Domain<T>) -> Self {
+        Self::from_fill_gaps(std::iter::empty(), domain)
+            .expect("nothing can overlap in an empty map")
+    }
+
+    /// Composes like: self(other(x)) -> output(x)
+    /// Unlike [RangeMap::left_join] both maps have to be total on the same
+    /// [Domain] (see [RangeMap::from_fill_gaps]) which makes the result total as well
+    /// and the composition independent of any seeds.
+    /// Composition is associative, so a chain can be composed once and then
+    /// applied to any seed set (via [RangeMap::image]) or single point
+    /// (via [RangeMap::get])
+    /// The result is [RangeMap::coalesce]d to keep the amount of pieces
+    /// from growing with every stage
+    fn compose(&self, other: &Self) -> Self {
+        debug_assert!(self
+            .covered_domain()
+            .is_some_and(|domain| other.is_total(domain)));
+        let domain = other
+            .covered_domain()
+            .expect("total maps cover their domain");
+        self.left_join(other)
+            .coalesce()
+            .into_total(domain)
+            .expect("composing total maps keeps the domain")
+    }
+
+    /// looks up where a single value is mapped to
+    /// fails with [RangeError::Unmapped] if `id` is outside the [Domain] the map
+    /// was built on, which includes [RangeId::MAX] even for [Domain::full]
+    fn get(&self, id: T) -> Result<T, RangeError<T>> {
+        self.lookup(id).ok_or(RangeError::Unmapped { id })
+    }
+}
+
+impl<T: RangeId, P: Piece<T>> RangeMap<T, Partial, P> {
+    /// explicit conversion, checks that `domain` is covered without holes
+    /// hands the map back unchanged if it isn't
+    fn into_total(self, domain: Domain<T>) -> Result<RangeMap<T, Total, P>, Self> {
+        if self.is_total(domain) {
+            Ok(RangeMap(self.0, PhantomData))
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// every total map is a (trivially) partial one
+impl<T: RangeId, P: Piece<T>> From<RangeMap<T, Total, P>> for RangeMap<T, Partial, P> {
+    fn from(map: RangeMap<T, Total, P>) -> Self {
+        RangeMap(map.0, PhantomData)
     }
 }
 
-impl RangeMap {
+impl<T: RangeId, C, P: Piece<T>> RangeMap<T, C, P> {
     /// Joins like: self(inner(x)) -> output(x)
     /// This means that the resulting map, maps `src` ranges from inner
     /// to `dest` ranges of self
-    fn left_join(&self, inner: &RangeMap) -> RangeMap {
+    /// Values missing in either of the maps are missing in the result
+    fn left_join<I>(&self, inner: &RangeMap<T, I, P>) -> RangeMap<T, Partial, P> {
         let outer = self;
         let result = inner
             .0
@@ -234,16 +700,119 @@ impl RangeMap {
                     .0
                     .iter()
                     .filter_map(|outer_range| inner_range.merge_with_overlap(outer_range))
+                    // a piece that runs backwards hits the outer pieces in reverse
+                    .sorted_by_key(|merged| merged.src())
             })
             .collect();
-        RangeMap(result)
+        RangeMap(result, PhantomData)
+    }
+
+    /// Merges neighbouring pieces that continue each other (see [Piece::extend])
+    /// and removes empty ones
+    /// Two coalesced maps of the same offset-only mapping function are equal
+    fn coalesce(self) -> Self {
+        let mut result: Vec<P> = Vec::with_capacity(self.0.len());
+        for mapping in self
+            .0
+            .into_iter()
+            .filter(|mapping| mapping.src() < mapping.src_end_excl())
+        {
+            match result.last_mut() {
+                Some(last) => match last.extend(&mapping) {
+                    Some(extended) => *last = extended,
+                    None => result.push(mapping),
+                },
+                None => result.push(mapping),
+            }
+        }
+        Self(result, PhantomData)
     }
 
+    /// whether the `src` ranges seamlessly cover `domain`
+    fn is_total(&self, domain: Domain<T>) -> bool {
+        self.covered_domain() == Some(domain)
+    }
+
+    /// the span of the `src` ranges if there are no holes between them
+    fn covered_domain(&self) -> Option<Domain<T>> {
+        let first = self.0.first()?;
+        let mut end_excl = first.src();
+        for mapping in &self.0 {
+            if mapping.src() != end_excl {
+                return None;
+            }
+            end_excl = mapping.src_end_excl();
+        }
+        Some(Domain {
+            start: first.src(),
+            end_excl,
+        })
+    }
+
+    /// looks up where a single value is mapped to
+    /// [None] if `id` is not part of any `src` range
+    fn lookup(&self, id: T) -> Option<T> {
+        let idx = self
+            .0
+            .partition_point(|mapping| mapping.src_end_excl() <= id);
+        self.0
+            .get(idx)
+            .filter(|mapping| mapping.src() <= id)
+            .map(|mapping| mapping.map_point(id))
+    }
+
+    /// the lowest id any id of `set` is mapped to, the same as the minimum of
+    /// [RangeMap::image] without building it (which scaled pieces can't)
+    fn lowest_dest(&self, set: &RangeSet<T>) -> Option<T> {
+        set.ranges()
+            .iter()
+            .flat_map(|range| {
+                self.0
+                    .iter()
+                    .filter_map(|mapping| mapping.lowest_dest(range))
+            })
+            .min()
+    }
+
+    /// The fast special case: the same map made of [RangeMapping]s,
+    /// [None] if any piece does more than add an offset
+    fn to_offset_only(&self) -> Option<RangeMap<T, C>> {
+        let pieces = self.0.iter().map(Piece::as_offset).collect::<Option<_>>()?;
+        Some(RangeMap(pieces, PhantomData))
+    }
+}
+
+impl<T: RangeId, C> RangeMap<T, C> {
+    /// every id `set` is mapped to, ids outside the `src` ranges are dropped
+    fn image(&self, set: &RangeSet<T>) -> RangeSet<T> {
+        set.ranges()
+            .iter()
+            .flat_map(|range| {
+                self.0
+                    .iter()
+                    .filter_map(|mapping| mapping.restrict_src(range.start, range.end))
+            })
+            .map(|mapping| mapping.dest..mapping.dest_end_excl())
+            .collect()
+    }
+
+    /// every id that is mapped into `set`
+    fn preimage(&self, set: &RangeSet<T>) -> RangeSet<T> {
+        set.ranges()
+            .iter()
+            .flat_map(|range| {
+                self.0
+                    .iter()
+                    .filter_map(|mapping| mapping.restrict_dest(range.start, range.end))
+            })
+            .map(|mapping| mapping.src..mapping.src_end_excl())
+            .collect()
+    }
     // TODO: can be optimized because arrays are sorted by in.start
     fn _find_in_overlapping_with_out<'a, 'b: 'a>(
         &'a self,
-        out_overlap_with: &'b RangeMapping,
-    ) -> impl Iterator<Item = (&'a RangeMapping, RangeOverlap)> {
+        out_overlap_with: &'b RangeMapping<T>,
+    ) -> impl Iterator<Item = (&'a RangeMapping<T>, RangeOverlap<T>)> {
         self.0.iter().filter_map(move |range| {
             range
                 .self_dest_overlaps_other_src(out_overlap_with)
@@ -252,116 +821,448 @@ impl RangeMap {
     }
 }
 
-struct RangeOverlap {
-    start: Id,
-    len: Id,
+struct RangeOverlap<T: RangeId> {
+    start: T,
+    len: T::Len,
 }
 
-struct Map(Vec<RangeMapping>);
-impl Map {
-    fn parse_section(s: &str) -> Self {
-        let mut lines = s.lines().peekable();
+/// What to do about `src` ranges of an almanac section that overlap
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum OverlapPolicy {
+    /// report all overlapping pairs of lines, see [Map::overlaps]
+    Error,
+    /// the line that comes first keeps the overlapping ids
+    FirstWins,
+    /// the line that comes last keeps the overlapping ids
+    LastWins,
+}
 
-        // take first line as header if it doesnt start with a digit
-        if let Some(maybe_header) = lines.peek() {
-            if maybe_header
-                .chars()
-                .next()
-                .is_some_and(|first_char| !first_char.is_digit(10))
-            {
-                lines.next().unwrap();
-            }
+impl FromStr for OverlapPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "first-wins" => Ok(Self::FirstWins),
+            "last-wins" => Ok(Self::LastWins),
+            other => Err(format!(
+                "invalid overlap policy '{other}', expected error, first-wins or last-wins"
+            )),
+        }
+    }
+}
+
+/// Two lines of the same section whose `src` ranges overlap
+/// `first_*` is always the line that comes first
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LineOverlap<T: RangeId> {
+    first_line_no: usize,
+    first_src: Range<T>,
+    second_line_no: usize,
+    second_src: Range<T>,
+}
+
+/// Every overlap of a section, reported by [OverlapPolicy::Error]
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct OverlapError<T: RangeId>(Vec<LineOverlap<T>>);
+
+impl<T: RangeId> Display for OverlapError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} overlapping pair(s) of src ranges:", self.0.len())?;
+        for overlap in &self.0 {
+            writeln!(
+                f,
+                "  line {} ({:?}) overlaps line {} ({:?})",
+                overlap.first_line_no,
+                overlap.first_src,
+                overlap.second_line_no,
+                overlap.second_src
+            )?;
         }
+        Ok(())
+    }
+}
+
+impl<T: RangeId> Error for OverlapError<T> {}
+
+/// format: `[(line number, mapping)]` in the order of the input
+struct Map<T: RangeId, P: Piece<T> = RangeMapping<T>>(Vec<(usize, P)>, PhantomData<T>);
+impl<T: RangeId, P: Piece<T>> Map<T, P> {
+    /// `first_line_no` is the (1-based) line number of the first line of `s`
+    /// within the whole input, it is only used for reporting
+    /// fails on the first invalid line and on sections without any mappings
+    fn parse_section(s: &str, first_line_no: usize) -> Result<Self, String> {
+        let mut lines = s.lines().zip(first_line_no..).peekable();
+
+        // take first line as header if it doesnt start with a (possibly negative) number
+        lines.next_if(|(maybe_header, _)| {
+            !maybe_header
+                .starts_with(|first_char: char| first_char.is_ascii_digit() || first_char == '-')
+        });
 
         let res = lines
-            .try_fold(vec![], |mut acc, line| {
-                acc.push(line.parse()?);
-                Ok::<_, String>(acc)
+            .map(|(line, line_no)| {
+                line.parse()
+                    .map(|mapping| (line_no, mapping))
+                    .map_err(|e| format!("line {line_no}: {e}"))
             })
-            .expect("invalid section");
+            .try_collect::<_, Vec<_>, _>()?;
 
-        assert_ne!(res.len(), 0);
+        if res.is_empty() {
+            return Err(format!(
+                "section starting at line {first_line_no} has no mappings"
+            ));
+        }
+        Ok(Self(res, PhantomData))
+    }
 
-        Self(res)
+    /// every pair of lines whose `src` ranges overlap
+    fn overlaps(&self) -> Vec<LineOverlap<T>> {
+        let by_src = self
+            .0
+            .iter()
+            .sorted_by_key(|(_, mapping)| mapping.src())
+            .collect_vec();
+
+        let mut res = vec![];
+        for (idx, (line_no, mapping)) in by_src.iter().enumerate() {
+            // only the following ones that start before this one ends can overlap
+            for (other_line_no, other) in by_src[idx + 1..]
+                .iter()
+                .take_while(|(_, other)| other.src() < mapping.src_end_excl())
+            {
+                let ((first_line_no, first), (second_line_no, second)) = if line_no < other_line_no
+                {
+                    ((line_no, mapping), (other_line_no, other))
+                } else {
+                    ((other_line_no, other), (line_no, mapping))
+                };
+                res.push(LineOverlap {
+                    first_line_no: *first_line_no,
+                    first_src: first.src()..first.src_end_excl(),
+                    second_line_no: *second_line_no,
+                    second_src: second.src()..second.src_end_excl(),
+                });
+            }
+        }
+        res.sort_by_key(|overlap| (overlap.first_line_no, overlap.second_line_no));
+        res
+    }
+
+    /// Applies `policy` to all overlapping lines, afterwards there are no overlaps
+    /// With [OverlapPolicy::FirstWins] and [OverlapPolicy::LastWins] lines that
+    /// lose ids get split into the parts that remain
+    fn resolve_overlaps(self, policy: OverlapPolicy) -> Result<Self, OverlapError<T>> {
+        let lines = match policy {
+            OverlapPolicy::Error => {
+                let overlaps = self.overlaps();
+                return if overlaps.is_empty() {
+                    Ok(self)
+                } else {
+                    Err(OverlapError(overlaps))
+                };
+            }
+            OverlapPolicy::FirstWins => self.0,
+            OverlapPolicy::LastWins => self.0.into_iter().rev().collect(),
+        };
+
+        // in the order of priority
+        let mut kept: Vec<(usize, P)> = Vec::with_capacity(lines.len());
+        // the ids earlier lines already map
+        let mut claimed = RangeSet::default();
+        for (line_no, mapping) in lines {
+            let src = RangeSet::new(std::iter::once(mapping.src()..mapping.src_end_excl()));
+            let remaining = src.difference(&claimed);
+            kept.extend(
+                remaining
+                    .ranges()
+                    .iter()
+                    .filter_map(|part| mapping.restrict_src(part.start, part.end))
+                    .map(|part| (line_no, part)),
+            );
+            claimed = claimed.union(&src);
+        }
+        kept.sort_by_key(|(line_no, mapping)| (*line_no, mapping.src()));
+        Ok(Self(kept, PhantomData))
     }
 }
 
-impl Into<RangeMap> for Map {
-    fn into(self) -> RangeMap {
-        RangeMap::from_fill_gaps(self.0.into_iter())
+impl<T: RangeId, P: Piece<T>> TryFrom<Map<T, P>> for RangeMap<T, Total, P> {
+    type Error = RangeError<T>;
+
+    /// the overlaps have to be resolved already, see [Map::resolve_overlaps]
+    fn try_from(map: Map<T, P>) -> Result<Self, Self::Error> {
+        RangeMap::from_fill_gaps(
+            map.0.into_iter().map(|(_, mapping)| mapping),
+            Domain::default(),
+        )
     }
 }
 
-const INPUT: &'static str = include_str!("./day05_input.txt");
+const INPUT: &str = include_str!("./day05_input.txt");
+
+/// the command line flags [main] hands on to [solve]
+struct Options<'a> {
+    /// explains where the lowest location comes from
+    trace_provenance: bool,
+    /// double checks part 2 by mapping every single seed (slow)
+    brute_force: bool,
+    /// draws the reducer chain to the given file
+    svg_path: Option<&'a String>,
+    excalidraw_path: Option<&'a String>,
+    /// caches the composed chain in the given directory
+    cache_dir: Option<&'a String>,
+    /// writes the composed chain to the given file (json if it ends in .json)
+    dump_path: Option<&'a String>,
+    /// the categories to convert between
+    from: &'a str,
+    to: &'a str,
+    /// how to deal with overlapping lines within a section
+    overlap_policy: OverlapPolicy,
+}
+
 pub fn main() {
+    let args = std::env::args().collect_vec();
+    let flag_value = |flag: &str| {
+        args.iter().position(|arg| arg == flag).map(|idx| {
+            args.get(idx + 1)
+                .expect("flag should be followed by a value")
+        })
+    };
+    let options = Options {
+        trace_provenance: args.iter().any(|arg| arg == "--provenance"),
+        brute_force: args.iter().any(|arg| arg == "--brute-force"),
+        svg_path: flag_value("--svg"),
+        excalidraw_path: flag_value("--excalidraw"),
+        cache_dir: flag_value("--cache"),
+        dump_path: flag_value("--dump"),
+        from: flag_value("--from").map_or("seed", String::as_str),
+        to: flag_value("--to").map_or("location", String::as_str),
+        overlap_policy: flag_value("--overlaps")
+            .map(|policy| policy.parse().unwrap())
+            .unwrap_or(OverlapPolicy::Error),
+    };
+    // allows a 4th `scale` column in the almanac (negative ones mirror ranges)
+    if args.iter().any(|arg| arg == "--affine") {
+        solve::<AffineMapping<Id>>(&options);
+    } else {
+        solve::<RangeMapping<Id>>(&options);
+    }
+}
+
+/// Both parts for an almanac whose lines are parsed as `P`
+fn solve<P: Piece<Id>>(options: &Options)
+where
+    RangeMap<Id, Total, P>: Snapshot,
+{
+    let Options { from, to, .. } = *options;
     let emptyline_re = regex::Regex::new(r"(?m)^\n").unwrap();
     let mut sections = emptyline_re.split(INPUT);
 
-    let seed_ranges = parse_seed_ranges(sections.next().unwrap()).unwrap();
-
-    let seed_to_soil = Map::parse_section(sections.next().unwrap());
-    let soil_to_fertilizer = Map::parse_section(sections.next().unwrap());
-    let fertilizer_to_water = Map::parse_section(sections.next().unwrap());
-    let water_to_light = Map::parse_section(sections.next().unwrap());
-    let light_to_temp = Map::parse_section(sections.next().unwrap());
-    let temp_to_humid = Map::parse_section(sections.next().unwrap());
-    let humid_to_location = Map::parse_section(sections.next().unwrap());
-
-    let reducer_chain: [RangeMap; 7] = [
-        seed_to_soil,
-        soil_to_fertilizer,
-        fertilizer_to_water,
-        water_to_light,
-        light_to_temp,
-        temp_to_humid,
-        humid_to_location,
-    ]
-    .map(|e| e.into());
-
-    let dummy_seed_map = RangeMap::from_seeds(seed_ranges.into_iter());
-
-    let final_map = reducer_chain
+    // 1-based line number of the first line of the next section
+    let mut line_no = 1;
+    let mut next_section = || {
+        let section = sections.next()?;
+        let first_line_no = line_no;
+        // +1 for the empty line the sections are separated by
+        line_no += section.lines().count() + 1;
+        Some((section, first_line_no))
+    };
+
+    let (seed_section, _) = next_section().expect("missing seed section");
+    let seeds = parse_seeds::<Id>(seed_section).unwrap();
+    let seed_ranges = parse_seed_ranges(seed_section).unwrap();
+
+    let mut graph = CategoryGraph::new();
+    while let Some((section, first_line_no)) = next_section() {
+        if section.trim().is_empty() {
+            continue;
+        }
+        let (src, dest) = parse_header(section.lines().next().unwrap_or_default())
+            .unwrap_or_else(|e| panic!("line {first_line_no}: {e}"));
+        let map = Map::<Id, P>::parse_section(section, first_line_no)
+            .unwrap_or_else(|e| panic!("{e}"))
+            .resolve_overlaps(options.overlap_policy)
+            .unwrap_or_else(|e| panic!("section starting at line {first_line_no}: {e}"));
+        let map: RangeMap<Id, Total, P> = map.try_into().expect("invalid almanac section");
+        graph.add(src, dest, map);
+    }
+
+    // the seed line lists ids of the `from` category
+    let path = graph
+        .path(from, to)
+        .unwrap_or_else(|e| panic!("can't convert {from} to {to}: {e}"));
+    let reducer_chain = path.iter().map(|conversion| &conversion.map).collect_vec();
+    let stage_names = path.iter().map(|conversion| conversion.name.as_str());
+    // drawing and tracing work on ranges, which scaled pieces don't map to
+    let offset_chain = reducer_chain
         .iter()
-        .fold(dummy_seed_map, |acc, curr_map| curr_map.left_join(&acc));
-
-    // let debug = final_map
-    //     .0
-    //     .iter()
-    //     .sorted_by_key(|e| e.dest)
-    //     .take(20)
-    //     .collect_vec();
-    //
-    // dbg!(debug);
-
-    // find the lowest start of any output range
-    let best_possible_result = final_map
-        .0
+        .map(|map| map.to_offset_only())
+        .collect::<Option<Vec<_>>>();
+    let offset_chain_for = |flag: &str| {
+        offset_chain
+            .as_ref()
+            .unwrap_or_else(|| panic!("{flag} needs a chain without scaled lines"))
+    };
+
+    if options.svg_path.is_some() || options.excalidraw_path.is_some() {
+        let flag = if options.svg_path.is_some() {
+            "--svg"
+        } else {
+            "--excalidraw"
+        };
+        let stages = stage_names
+            .clone()
+            .zip(offset_chain_for(flag))
+            .collect_vec();
+        let diagram = Diagram::from_chain(&stages);
+        if let Some(path) = options.svg_path {
+            std::fs::write(path, diagram.to_svg()).expect("failed to write svg");
+        }
+        if let Some(path) = options.excalidraw_path {
+            std::fs::write(path, diagram.to_excalidraw()).expect("failed to write excalidraw");
+        }
+    }
+
+    // from -> to, independent of the seeds themselves
+    let compose_chain = || {
+        reducer_chain.iter().zip(stage_names.clone()).fold(
+            RangeMap::identity(Domain::default()),
+            |acc, (curr_map, stage_name)| {
+                let composed = curr_map.compose(&acc);
+                println!(
+                    "{stage_name:>23}: {:>3} pieces, composed chain: {:>3} pieces",
+                    curr_map.0.len(),
+                    composed.0.len()
+                );
+                composed
+            },
+        )
+    };
+    let seed_to_location = match options.cache_dir {
+        Some(dir) => {
+            let policy = format!("{:?}", options.overlap_policy);
+            let key = snapshot::input_hash(&[INPUT, from, to, &policy]);
+            snapshot::cached(Path::new(dir), key, compose_chain)
+        }
+        None => compose_chain(),
+    };
+    if let Some(path) = options.dump_path {
+        snapshot::save(Path::new(path), &seed_to_location).expect("failed to dump chain");
+    }
+
+    let lowest_single_seed_location = seeds
         .iter()
-        .min_by_key(|e| e.dest)
-        .expect("mapping should not be empty")
-        .dest;
+        .copied()
+        .map(|seed| {
+            seed_to_location
+                .get(seed)
+                .expect("seeds should be in the domain")
+        })
+        .min()
+        .expect("seeds should not be empty");
+    dbg!(lowest_single_seed_location);
+
+    let seed_set = RangeSet::new(seed_ranges.iter().cloned());
+    let best_possible_result = seed_to_location
+        .lowest_dest(&seed_set)
+        .expect("seeds should not be empty");
     dbg!(best_possible_result);
+
+    if let Some(seed_to_location) = seed_to_location.to_offset_only() {
+        debug_assert!(seed_to_location
+            .image(&seeds.iter().map(|&seed| seed..seed + 1).collect())
+            .contains(lowest_single_seed_location));
+        let locations = seed_to_location.image(&seed_set);
+        debug_assert_eq!(locations.min(), Some(best_possible_result));
+        // a seed set can be pushed through the chain stage by stage just as well
+        if let Some(offset_chain) = &offset_chain {
+            debug_assert_eq!(
+                offset_chain
+                    .iter()
+                    .fold(seed_set.clone(), |set, map| map.image(&set)),
+                locations
+            );
+        }
+
+        // going backwards: which seeds end up at the lowest location
+        let best_seeds = seed_to_location
+            .preimage(&RangeSet::new(std::iter::once(
+                best_possible_result..best_possible_result + 1,
+            )))
+            .intersection(&seed_set);
+        dbg!(best_seeds.min());
+    } else {
+        println!("the composed chain scales, the best seeds aren't traced back");
+    }
+    if options.brute_force {
+        let brute_force_result = brute_force::lowest_location(&seed_ranges, &reducer_chain)
+            .expect("every seed should have a location");
+        assert_eq!(brute_force_result, best_possible_result);
+    }
+
+    if options.trace_provenance {
+        let traced = offset_chain_for("--provenance")
+            .iter()
+            .zip(stage_names)
+            .fold(
+                TracedRangeMap::new(&seed_set, from),
+                |acc, (curr_map, stage_name)| acc.left_join(curr_map, stage_name),
+            );
+        print!(
+            "{}",
+            traced
+                .explain_lowest()
+                .expect("mapping should not be empty")
+        );
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::day05::RangeMapping;
 
-    use super::Id;
-    use super::RangeMap;
+    use std::marker::PhantomData;
+
+    use super::{
+        Domain, Id, LineOverlap, Map, OverlapError, OverlapPolicy, Partial, Piece, RangeError,
+        RangeMap, RangeSet, SeedRange,
+    };
+
+    /// The maps of the example almanac from the puzzle description, in order
+    /// from seed-to-soil to humidity-to-location
+    pub(super) fn example_chain() -> Vec<RangeMap<Id>> {
+        [
+            "50 98 2\n52 50 48",
+            "0 15 37\n37 52 2\n39 0 15",
+            "49 53 8\n0 11 42\n42 0 7\n57 7 4",
+            "88 18 7\n18 25 70",
+            "45 77 23\n81 45 19\n68 64 13",
+            "0 69 1\n1 0 69",
+            "60 56 37\n56 93 4",
+        ]
+        .into_iter()
+        .map(|section| Map::parse_section(section, 1).unwrap().try_into().unwrap())
+        .collect()
+    }
+
+    /// the domain before negative ids were supported
+    fn non_negative() -> Domain<Id> {
+        Domain::new(0, Id::MAX).unwrap()
+    }
     #[test]
     fn create_range_map_gaps() {
         let ranges = [
-            RangeMapping::from_len(20, 3, 3),
-            RangeMapping::from_len(5, 10, 10),
+            RangeMapping::from_len(20, 3, 3).unwrap(),
+            RangeMapping::from_len(5, 10, 10).unwrap(),
         ];
         let ranges2 = [
-            RangeMapping::from_len(0, 2, 2),
-            RangeMapping::from_len(2, 100, 2),
-            RangeMapping::from_len(4, 10, 2),
+            RangeMapping::from_len(0, 2, 2).unwrap(),
+            RangeMapping::from_len(2, 100, 2).unwrap(),
+            RangeMapping::from_len(4, 10, 2).unwrap(),
         ];
-        let created = RangeMap::from_fill_gaps(ranges.into_iter());
-        let created2 = RangeMap::from_fill_gaps(ranges2.into_iter());
+        let created = RangeMap::from_fill_gaps(ranges.into_iter(), non_negative()).unwrap();
+        let created2 = RangeMap::from_fill_gaps(ranges2.into_iter(), non_negative()).unwrap();
 
         assert_eq!(created.0.len(), 5);
         debug_assert_eq!(created.0[0], (0..5, 0..5));
@@ -378,57 +1279,579 @@ mod test {
     }
 
     #[test]
-    fn create_range_map_seeds() {
-        let seed_ranges = vec![10..15, 0..2, 5..8];
+    fn create_range_set() {
+        let seed_ranges: Vec<SeedRange<Id>> = vec![10..15, 0..2, 5..8, 12..20, 3..3, 2..4];
+
+        let created = RangeSet::new(seed_ranges);
+
+        assert_eq!(created.ranges(), [0..4, 5..8, 10..20]);
+        assert_eq!(created.min(), Some(0));
+        assert!(created.contains(3) && !created.contains(4) && created.contains(19));
+        assert_eq!(RangeSet::<Id>::new([]), RangeSet::default());
+    }
 
-        let created = RangeMap::from_seeds(seed_ranges.into_iter());
+    #[test]
+    fn range_set_ops() {
+        let a = RangeSet::<Id>::new([0..10, 20..30]);
+        let b = RangeSet::new([5..25, 40..50]);
 
-        assert_eq!(created.0.len(), 3);
-        debug_assert_eq!(created.0[0], (0..2, 0..2));
-        debug_assert_eq!(created.0[1], (5..8, 5..8));
-        debug_assert_eq!(created.0[2], (10..15, 10..15));
+        assert_eq!(a.union(&b).ranges(), [0..30, 40..50]);
+        assert_eq!(a.intersection(&b).ranges(), [5..10, 20..25]);
+        assert_eq!(a.difference(&b).ranges(), [0..5, 25..30]);
+        assert_eq!(b.difference(&a).ranges(), [10..20, 40..50]);
+        assert_eq!(
+            a.complement(Domain::new(-5, 35).unwrap()).ranges(),
+            [-5..0, 10..20, 30..35]
+        );
+        assert_eq!(a.complement(Domain::full()).complement(Domain::full()), a);
     }
 
     #[test]
     fn merge_with_overlap() {
-        let r1 = RangeMapping::from_len(0, 5, 3);
-        let r2 = RangeMapping::from_len(5, 10, 5);
-        let r3 = RangeMapping::from_len(2, 0, 2);
+        let r1 = RangeMapping::<Id>::from_len(0, 5, 3).unwrap();
+        let r2 = RangeMapping::from_len(5, 10, 5).unwrap();
+        let r3 = RangeMapping::from_len(2, 0, 2).unwrap();
 
         debug_assert_eq!(r1.merge_with_overlap(&r1), None);
         debug_assert_eq!(
             r1.merge_with_overlap(&r2),
-            Some(RangeMapping::from_len(0, 10, 3))
+            Some(RangeMapping::from_len(0, 10, 3).unwrap())
         );
         debug_assert_eq!(r1.merge_with_overlap(&r3), None)
     }
 
     #[test]
-    fn left_join_seeds() {
-        let seed_ranges = vec![10..15, 0..2, 5..8];
+    fn image_of_seeds() {
+        let seed_ranges: Vec<SeedRange<Id>> = vec![10..15, 0..2, 5..8];
         let ranges = [
-            RangeMapping::from_len(20, 3, 3),
-            RangeMapping::from_len(5, 10, 2),
-            RangeMapping::from_len(8, 80, 3),
-            RangeMapping::from_len(11, 110, 1),
-            RangeMapping::from_len(14, 140, 3),
+            RangeMapping::from_len(20, 3, 3).unwrap(),
+            RangeMapping::from_len(5, 10, 2).unwrap(),
+            RangeMapping::from_len(8, 80, 3).unwrap(),
+            RangeMapping::from_len(11, 110, 1).unwrap(),
+            RangeMapping::from_len(14, 140, 3).unwrap(),
         ];
 
-        let seeds = RangeMap::from_seeds(seed_ranges.into_iter());
-        let created = RangeMap::from_fill_gaps(ranges.into_iter());
+        let seeds = RangeSet::new(seed_ranges);
+        let created = RangeMap::from_fill_gaps(ranges.into_iter(), non_negative()).unwrap();
+
+        let image = created.image(&seeds);
+        assert_eq!(
+            image.ranges(),
+            [0..2, 7..8, 10..14, 82..83, 110..111, 140..141]
+        );
+        // 10 is the image of 5 as well as of 10, the gaps map to themselves
+        assert_eq!(
+            created.preimage(&image).ranges(),
+            [0..2, 5..8, 10..15, 82..83, 110..111, 140..141]
+        );
+        assert_eq!(
+            created.preimage(&RangeSet::new([0..1, 82..83])).ranges(),
+            [0..1, 10..11, 82..83]
+        );
+    }
+
+    macro_rules! full_domain_test {
+        ($name:ident, $id:ty) => {
+            #[test]
+            fn $name() {
+                let top_start = <$id>::MAX - 10;
+                let map = RangeMap::<$id>::from_fill_gaps(
+                    [
+                        RangeMapping::from_len(top_start, 0, 10).unwrap(),
+                        RangeMapping::from_len(0, 10, 5).unwrap(),
+                    ]
+                    .into_iter(),
+                    Domain::new(0, <$id>::MAX).unwrap(),
+                )
+                .unwrap();
+
+                assert!(map.is_total(Domain::new(0, <$id>::MAX).unwrap()));
+                debug_assert_eq!(map.0[0], (0..5, 10..15));
+                debug_assert_eq!(map.0[1], (5..top_start, 5..top_start));
+                debug_assert_eq!(map.0[2], (top_start..<$id>::MAX, 0..10));
+                assert_eq!(map.get(<$id>::MAX - 1).unwrap(), 9);
+
+                let twice = map.compose(&map);
+                assert!(twice.is_total(Domain::new(0, <$id>::MAX).unwrap()));
+                assert_eq!(twice.get(<$id>::MAX - 10).unwrap(), 10);
+                assert_eq!(twice.get(<$id>::MAX - 5).unwrap(), 5);
+                assert_eq!(twice.get(2).unwrap(), 12);
+
+                // MAX is the exclusive end of every domain, identity fill included
+                let identity = RangeMap::<$id>::identity(Domain::full());
+                assert_eq!(identity.get(<$id>::MAX - 1).unwrap(), <$id>::MAX - 1);
+                for map in [&map, &twice, &identity] {
+                    assert_eq!(
+                        map.get(<$id>::MAX),
+                        Err(RangeError::Unmapped { id: <$id>::MAX })
+                    );
+                }
+            }
+        };
+    }
+    full_domain_test!(full_domain_u32, u32);
+    full_domain_test!(full_domain_u64, u64);
+    full_domain_test!(full_domain_i64, i64);
+    full_domain_test!(full_domain_u128, u128);
 
-        let joined = created.left_join(&seeds);
+    #[test]
+    fn range_errors() {
+        assert_eq!(
+            RangeMapping::<Id>::from_len(3, 4, 0),
+            Err(RangeError::Empty {
+                start: 3,
+                end_excl: 3
+            })
+        );
+        assert_eq!(
+            RangeMapping::<Id>::from_len(0, Id::MAX - 1, 2),
+            Err(RangeError::Overflow {
+                start: Id::MAX - 1,
+                len: 2
+            })
+        );
+        assert_eq!(
+            RangeMapping::<u32>::from_len(u32::MAX, 0, 1),
+            Err(RangeError::Overflow {
+                start: u32::MAX,
+                len: 1
+            })
+        );
+        assert_eq!(
+            RangeMapping::<Id>::dflt_from_bounds(5, 5),
+            Err(RangeError::Empty {
+                start: 5,
+                end_excl: 5
+            })
+        );
+        assert!(Domain::<Id>::new(Id::MAX, Id::MAX).is_err());
+        assert!(RangeMapping::<Id>::from_len(0, Id::MAX - 2, 2).is_ok());
+
+        assert_eq!(
+            RangeMap::<Id>::from_fill_gaps(
+                [
+                    RangeMapping::from_len(0, 10, 5).unwrap(),
+                    RangeMapping::from_len(3, 20, 5).unwrap(),
+                ]
+                .into_iter(),
+                non_negative()
+            ),
+            Err(RangeError::Overlap {
+                start: 3,
+                previous_end_excl: 5
+            })
+        );
+        assert!("1 2 0".parse::<RangeMapping<Id>>().is_err());
+        assert_eq!(
+            "1 -2 5".parse::<RangeMapping<Id>>(),
+            Ok(RangeMapping::from_len(-2, 1, 5).unwrap())
+        );
+    }
+
+    #[test]
+    fn fill_gaps_domain_bounds() {
+        // negative ids are mapped as well
+        let full = RangeMap::<Id>::from_fill_gaps(
+            [RangeMapping::from_len(-10, 5, 5).unwrap()].into_iter(),
+            Domain::full(),
+        )
+        .unwrap();
+        assert_eq!(full.0.len(), 3);
+        debug_assert_eq!(full.0[0], (Id::MIN..-10, Id::MIN..-10));
+        debug_assert_eq!(full.0[1], (-10..-5, 5..10));
+        debug_assert_eq!(full.0[2], (-5..Id::MAX, -5..Id::MAX));
+        assert_eq!(full.get(Id::MIN).unwrap(), Id::MIN);
+        assert_eq!(full.get(-7).unwrap(), 8);
+        assert_eq!(full.get(Id::MAX - 1).unwrap(), Id::MAX - 1);
+        // MAX is the exclusive end of every domain
+        assert_eq!(full.lookup(Id::MAX), None);
+
+        // mappings touching both bounds don't get fillers outside of them
+        let edges = RangeMap::<Id>::from_fill_gaps(
+            [
+                RangeMapping::from_len(Id::MAX - 10, -10, 10).unwrap(),
+                RangeMapping::from_len(Id::MIN, 0, 10).unwrap(),
+            ]
+            .into_iter(),
+            Domain::full(),
+        )
+        .unwrap();
+        assert!(edges.is_total(Domain::full()));
+        assert_eq!(edges.0.len(), 3);
+        debug_assert_eq!(edges.0[0], (Id::MIN..Id::MIN + 10, 0..10));
         debug_assert_eq!(
-            joined,
-            RangeMap(vec![
-                RangeMapping::dflt_from_bounds(0, 2),
-                RangeMapping::from_len(5, 10, 2),
-                RangeMapping::dflt_from_bounds(7, 8),
-                RangeMapping::from_len(10, 82, 1),
-                RangeMapping::from_len(11, 110, 1),
-                RangeMapping::dflt_from_bounds(12, 14),
-                RangeMapping::from_len(14, 140, 1),
+            edges.0[1],
+            (Id::MIN + 10..Id::MAX - 10, Id::MIN + 10..Id::MAX - 10)
+        );
+        debug_assert_eq!(edges.0[2], (Id::MAX - 10..Id::MAX, -10..0));
+        let twice = edges.compose(&edges);
+        assert!(twice.is_total(Domain::full()));
+        assert_eq!(twice.get(Id::MIN).unwrap(), 0);
+        assert_eq!(twice.get(Id::MAX - 1).unwrap(), -1);
+
+        // custom domains
+        let small = Domain::new(10, 20).unwrap();
+        let created = RangeMap::<Id>::from_fill_gaps(
+            [RangeMapping::from_len(12, 15, 3).unwrap()].into_iter(),
+            small,
+        )
+        .unwrap();
+        assert!(created.is_total(small));
+        assert!(!created.is_total(Domain::full()));
+        assert_eq!(created.lookup(9), None);
+        assert_eq!(created.get(13).unwrap(), 16);
+        assert_eq!(created.lookup(20), None);
+        assert_eq!(
+            RangeMap::identity(small).compose(&created),
+            created.clone().coalesce()
+        );
+        for outside in [
+            RangeMapping::from_len(12, 18, 3).unwrap(),
+            RangeMapping::from_len(8, 12, 3).unwrap(),
+        ] {
+            assert_eq!(
+                RangeMap::from_fill_gaps([outside].into_iter(), small),
+                Err(RangeError::OutOfDomain {
+                    src: outside.src,
+                    src_end_excl: outside.src_end_excl(),
+                    domain: small
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn section_overlaps() {
+        let section = "a-to-b map:\n100 0 10\n200 5 10\n300 20 5\n400 8 14";
+        let parse = || Map::<Id>::parse_section(section, 10).unwrap();
+        let overlap = |first_line_no, first_src, second_line_no, second_src| LineOverlap {
+            first_line_no,
+            first_src,
+            second_line_no,
+            second_src,
+        };
+
+        let error = parse()
+            .resolve_overlaps(OverlapPolicy::Error)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error,
+            OverlapError(vec![
+                overlap(11, 0..10, 12, 5..15),
+                overlap(11, 0..10, 14, 8..22),
+                overlap(12, 5..15, 14, 8..22),
+                overlap(13, 20..25, 14, 8..22),
             ])
+        );
+        assert!(error
+            .to_string()
+            .contains("line 13 (20..25) overlaps line 14 (8..22)"));
+
+        let first_wins = parse().resolve_overlaps(OverlapPolicy::FirstWins).unwrap();
+        assert!(first_wins.overlaps().is_empty());
+        assert_eq!(
+            first_wins.0,
+            vec![
+                (11, RangeMapping::from_len(0, 100, 10).unwrap()),
+                (12, RangeMapping::from_len(10, 205, 5).unwrap()),
+                (13, RangeMapping::from_len(20, 300, 5).unwrap()),
+                (14, RangeMapping::from_len(15, 407, 5).unwrap()),
+            ]
+        );
+
+        let last_wins = parse().resolve_overlaps(OverlapPolicy::LastWins).unwrap();
+        assert!(last_wins.overlaps().is_empty());
+        assert_eq!(
+            last_wins.0,
+            vec![
+                (11, RangeMapping::from_len(0, 100, 5).unwrap()),
+                (12, RangeMapping::from_len(5, 200, 3).unwrap()),
+                (13, RangeMapping::from_len(22, 302, 3).unwrap()),
+                (14, RangeMapping::from_len(8, 400, 14).unwrap()),
+            ]
+        );
+        assert!(RangeMap::try_from(last_wins).is_ok());
+
+        // without overlaps every policy keeps the section as is
+        let clean = "1 0 5\n2 5 5";
+        for policy in ["error", "first-wins", "last-wins"] {
+            let resolved = Map::<Id>::parse_section(clean, 1)
+                .unwrap()
+                .resolve_overlaps(policy.parse().unwrap())
+                .unwrap();
+            assert_eq!(resolved.0, Map::parse_section(clean, 1).unwrap().0);
+        }
+    }
+
+    #[test]
+    fn section_errors() {
+        assert_eq!(
+            Map::<Id>::parse_section("a-to-b map:\n1 0 5\n1 2", 4).err(),
+            Some("line 6: expected 3 uints but got '1 2'".to_string())
+        );
+        assert_eq!(
+            Map::<Id>::parse_section("a-to-b map:", 4).err(),
+            Some("section starting at line 4 has no mappings".to_string())
+        );
+    }
+
+    #[test]
+    fn coalesce_pieces() {
+        let map = RangeMap::<Id, Partial>(
+            vec![
+                RangeMapping::from_len(0, 10, 2).unwrap(),
+                RangeMapping::from_len(2, 12, 3).unwrap(),
+                RangeMapping {
+                    src: 5,
+                    dest: 0,
+                    len: 0,
+                },
+                RangeMapping::from_len(5, 15, 1).unwrap(),
+                RangeMapping::dflt_from_bounds(6, 10).unwrap(),
+                RangeMapping::dflt_from_bounds(10, 20).unwrap(),
+                RangeMapping::from_len(20, 0, 1).unwrap(),
+            ],
+            PhantomData,
+        );
+
+        debug_assert_eq!(
+            map.coalesce().0,
+            vec![
+                RangeMapping::from_len(0, 10, 6).unwrap(),
+                RangeMapping::dflt_from_bounds(6, 20).unwrap(),
+                RangeMapping::from_len(20, 0, 1).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn compose_total() {
+        let first = RangeMap::<Id>::from_fill_gaps(
+            [
+                RangeMapping::from_len(0, 10, 5).unwrap(),
+                RangeMapping::from_len(10, 0, 5).unwrap(),
+            ]
+            .into_iter(),
+            non_negative(),
         )
+        .unwrap();
+        let second = RangeMap::from_fill_gaps(
+            [RangeMapping::from_len(3, 12, 9).unwrap()].into_iter(),
+            non_negative(),
+        )
+        .unwrap();
+
+        let composed = second.compose(&first);
+        assert!(composed.is_total(non_negative()));
+        debug_assert_eq!(
+            composed.0,
+            vec![
+                RangeMapping::from_len(0, 19, 2).unwrap(),
+                RangeMapping::from_len(2, 12, 3).unwrap(),
+                RangeMapping::from_len(5, 14, 5).unwrap(),
+                RangeMapping::from_len(10, 0, 3).unwrap(),
+                RangeMapping::from_len(13, 12, 2).unwrap(),
+                RangeMapping::dflt_from_bounds(15, Id::MAX).unwrap(),
+            ]
+        );
+        assert_eq!(composed.get(0).unwrap(), 19);
+        assert_eq!(composed.get(4).unwrap(), 14);
+        assert_eq!(composed.get(11).unwrap(), 1);
+        assert_eq!(composed.get(14).unwrap(), 13);
+        assert_eq!(composed.get(1000).unwrap(), 1000);
+        assert_eq!(composed.lookup(-1), None);
+        // the result of the join is only partial until checked
+        let joined = second.left_join(&first).coalesce();
+        assert_eq!(joined.0, composed.0);
+        let joined = joined.into_total(Domain::full()).unwrap_err();
+        assert_eq!(joined.into_total(non_negative()), Ok(composed.clone()));
+        assert_eq!(
+            RangeMap::<Id, Partial>::from(composed.clone()).0,
+            composed.0
+        );
+    }
+
+    /// Randomized checks of the range algebra against a naive interpreter
+    /// that maps every id of a small domain one by one
+    mod props {
+        use std::{marker::PhantomData, ops::Range};
+
+        use itertools::Itertools;
+        use proptest::prelude::*;
+
+        use super::super::{
+            Domain, Id, Partial, Piece, RangeMap, RangeMapping, RangeSet, SeedRange,
+        };
+
+        /// all generated ranges lie within this domain (which includes negative ids)
+        const DOMAIN: Range<Id> = -40..128;
+
+        /// non-overlapping mappings within [DOMAIN] in random order
+        fn raw_mappings() -> impl Strategy<Value = Vec<RangeMapping<Id>>> {
+            prop::collection::vec((0..5 as Id, 1..10u64, -30..60 as Id), 0..6)
+                .prop_map(|pieces| {
+                    let mut src = -20;
+                    pieces
+                        .into_iter()
+                        .map(|(gap, len, dest)| {
+                            src += gap;
+                            let mapping = RangeMapping::from_len(src, dest, len).unwrap();
+                            src += len as Id;
+                            mapping
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .prop_shuffle()
+        }
+
+        /// gaps get filled
+        fn total_map() -> impl Strategy<Value = RangeMap<Id>> {
+            raw_mappings().prop_map(|mappings| {
+                RangeMap::from_fill_gaps(mappings.into_iter(), Domain::full()).unwrap()
+            })
+        }
+
+        /// possibly overlapping seed ranges within [DOMAIN]
+        fn seed_ranges() -> impl Strategy<Value = Vec<SeedRange<Id>>> {
+            prop::collection::vec((-40..100 as Id, 1..20 as Id), 1..5).prop_map(|ranges| {
+                ranges
+                    .into_iter()
+                    .map(|(start, len)| start..start + len)
+                    .collect()
+            })
+        }
+
+        /// the almanac rules, applied to a single id
+        fn naive(mappings: &[RangeMapping<Id>], id: Id) -> Id {
+            mappings
+                .iter()
+                .find(|mapping| (mapping.src..mapping.src_end_excl()).contains(&id))
+                .map_or(id, |mapping| mapping.dest + (id - mapping.src))
+        }
+
+        /// every `dest` a (partial, possibly overlapping) map assigns to `id`
+        fn all_dests(map: &RangeMap<Id, Partial>, id: Id) -> Vec<Id> {
+            map.0
+                .iter()
+                .filter(|mapping| (mapping.src..mapping.src_end_excl()).contains(&id))
+                .map(|mapping| mapping.dest + (id - mapping.src))
+                .sorted()
+                .collect()
+        }
+
+        fn assert_same_points(left: &RangeMap<Id>, right: &RangeMap<Id>) {
+            for id in DOMAIN.chain([Id::MIN, Id::MAX - 1]) {
+                assert_eq!(left.get(id), right.get(id), "differs at {id}");
+            }
+        }
+
+        proptest! {
+            #[test]
+            fn fill_gaps_matches_naive(mappings in raw_mappings()) {
+                let map = RangeMap::from_fill_gaps(mappings.clone().into_iter(), Domain::full()).unwrap();
+                prop_assert!(map.is_total(Domain::full()));
+                for id in DOMAIN {
+                    prop_assert_eq!(map.get(id).unwrap(), naive(&mappings, id), "at {}", id);
+                }
+            }
+
+            #[test]
+            fn left_join_matches_naive(outer in raw_mappings(), inner in raw_mappings()) {
+                let outer_map = RangeMap::from_fill_gaps(outer.clone().into_iter(), Domain::full()).unwrap();
+                // partial, only the explicit lines of the section
+                let inner_map = RangeMap::<Id, Partial>(inner.clone(), PhantomData);
+                let joined = outer_map.left_join(&inner_map);
+                for id in DOMAIN {
+                    let expected = inner
+                        .iter()
+                        .filter(|mapping| (mapping.src..mapping.src_end_excl()).contains(&id))
+                        .map(|_| naive(&outer, naive(&inner, id)))
+                        .collect_vec();
+                    prop_assert_eq!(all_dests(&joined, id), expected, "at {}", id);
+                }
+            }
+
+            #[test]
+            fn set_ops_match_naive(a in seed_ranges(), b in seed_ranges()) {
+                let (set_a, set_b) = (RangeSet::new(a.clone()), RangeSet::new(b.clone()));
+                let (union, intersection) = (set_a.union(&set_b), set_a.intersection(&set_b));
+                let difference = set_a.difference(&set_b);
+                let complement = set_a.complement(Domain::full());
+                for id in DOMAIN.chain([Id::MIN, Id::MAX - 1]) {
+                    let in_a = a.iter().any(|range| range.contains(&id));
+                    let in_b = b.iter().any(|range| range.contains(&id));
+                    prop_assert_eq!(union.contains(id), in_a || in_b, "at {}", id);
+                    prop_assert_eq!(intersection.contains(id), in_a && in_b, "at {}", id);
+                    prop_assert_eq!(difference.contains(id), in_a && !in_b, "at {}", id);
+                    prop_assert_eq!(complement.contains(id), !in_a, "at {}", id);
+                }
+                // normalized: sorted, disjoint and not touching
+                for set in [union, intersection, difference, complement] {
+                    prop_assert!(set.ranges().iter().all(|range| !range.is_empty()));
+                    prop_assert!(set.ranges().windows(2).all(|w| w[0].end < w[1].start));
+                }
+            }
+
+            #[test]
+            fn image_matches_naive(mappings in raw_mappings(), seeds in seed_ranges()) {
+                let map = RangeMap::from_fill_gaps(mappings.clone().into_iter(), Domain::full()).unwrap();
+                let seed_set = RangeSet::new(seeds);
+                let image = map.image(&seed_set);
+                let expected = RangeSet::new(
+                    DOMAIN
+                        .filter(|id| seed_set.contains(*id))
+                        .map(|id| naive(&mappings, id))
+                        .map(|dest| dest..dest + 1),
+                );
+                prop_assert_eq!(&image, &expected);
+
+                let preimage = map.preimage(&image);
+                for id in DOMAIN {
+                    prop_assert_eq!(
+                        preimage.contains(id),
+                        image.contains(naive(&mappings, id)),
+                        "at {}",
+                        id
+                    );
+                }
+            }
+
+            #[test]
+            fn compose_matches_naive(outer in raw_mappings(), inner in raw_mappings()) {
+                let composed = RangeMap::from_fill_gaps(outer.clone().into_iter(), Domain::full())
+                    .unwrap()
+                    .compose(&RangeMap::from_fill_gaps(inner.clone().into_iter(), Domain::full()).unwrap());
+                prop_assert!(composed.is_total(Domain::full()));
+                for id in DOMAIN {
+                    let expected = naive(&outer, naive(&inner, id));
+                    prop_assert_eq!(composed.get(id).unwrap(), expected, "at {}", id);
+                }
+            }
+
+            #[test]
+            fn compose_identity(map in total_map()) {
+                assert_same_points(&map.compose(&RangeMap::identity(Domain::full())), &map);
+                assert_same_points(&RangeMap::identity(Domain::full()).compose(&map), &map);
+            }
+
+            #[test]
+            fn compose_associative(a in total_map(), b in total_map(), c in total_map()) {
+                let left = a.compose(&b).compose(&c);
+                let right = a.compose(&b.compose(&c));
+                prop_assert!(left.is_total(Domain::full()) && right.is_total(Domain::full()));
+                assert_same_points(&left, &right);
+                // coalesced maps are canonical
+                prop_assert_eq!(left, right);
+            }
+
+            #[test]
+            fn coalesce_keeps_points(map in total_map()) {
+                let coalesced = map.clone().coalesce();
+                assert_same_points(&coalesced, &map);
+                prop_assert!(coalesced.0.len() <= map.0.len());
+                prop_assert_eq!(coalesced, map.compose(&RangeMap::identity(Domain::full())));
+            }
+        }
     }
 }
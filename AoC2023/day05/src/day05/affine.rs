@@ -0,0 +1,477 @@
+use std::{num::NonZeroI128, ops::Range, str::FromStr};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::{Domain, Piece, RangeError, RangeId, RangeMap, RangeMapping, Total};
+
+const ONE: NonZeroI128 = NonZeroI128::new(1).unwrap();
+
+/// Ids whose arithmetic fits into an `i128` (all of them but `u128`),
+/// scaled positions are computed there and narrowed back afterwards
+pub(super) trait WideId: RangeId {
+    fn widen(self) -> i128;
+    fn narrow(wide: i128) -> Option<Self>;
+    fn widen_len(len: Self::Len) -> i128;
+    fn narrow_len(wide: i128) -> Option<Self::Len>;
+}
+
+macro_rules! impl_wide_id {
+    ($id:ty) => {
+        impl WideId for $id {
+            fn widen(self) -> i128 {
+                self.into()
+            }
+            fn narrow(wide: i128) -> Option<Self> {
+                wide.try_into().ok()
+            }
+            fn widen_len(len: Self::Len) -> i128 {
+                len.into()
+            }
+            fn narrow_len(wide: i128) -> Option<Self::Len> {
+                wide.try_into().ok()
+            }
+        }
+    };
+}
+impl_wide_id!(u32);
+impl_wide_id!(u64);
+impl_wide_id!(i64);
+
+/// `floor(a / b)`, `b` must not be 0
+fn div_floor(a: i128, b: i128) -> i128 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        quotient - 1
+    } else {
+        quotient
+    }
+}
+
+/// `ceil(a / b)`, `b` must not be 0
+fn div_ceil(a: i128, b: i128) -> i128 {
+    -div_floor(-a, b)
+}
+
+/// `dest + scale * steps`, [None] if that doesn't fit into `T`
+fn scaled<T: WideId>(dest: T, scale: NonZeroI128, steps: i128) -> Option<T> {
+    scale
+        .get()
+        .checked_mul(steps)?
+        .checked_add(T::widen(dest))
+        .and_then(T::narrow)
+}
+
+/// Like a [RangeMapping], but `x` in `src..src + len` is mapped to
+/// `dest + scale * (x - src)`, i.e. `dest` is still where `src` ends up
+/// A negative `scale` mirrors the range, `|scale| > 1` stretches it (the
+/// values in between are skipped, so the image is no longer a range)
+///
+/// The scale of a single id doesn't matter and is always 1, which keeps every
+/// other scale below the width of `T`: merging two pieces can't overflow
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub(super) struct AffineMapping<T: WideId> {
+    src: T,
+    dest: T,
+    len: T::Len,
+    scale: NonZeroI128,
+}
+
+/// A map of (possibly) scaled pieces, like [RangeMap::left_join] joining them
+/// only ever narrows down their `src` range and combines their functions,
+/// so the result stays affine
+pub(super) type AffineMap<T> = RangeMap<T, Total, AffineMapping<T>>;
+
+impl<T: WideId> AffineMapping<T> {
+    /// fails if the `src` range is empty or if the range or any of the values
+    /// it's mapped to don't fit into `T`
+    pub(super) fn new(
+        src: T,
+        dest: T,
+        len: T::Len,
+        scale: NonZeroI128,
+    ) -> Result<Self, RangeError<T>> {
+        // with the regular checks covering src and the range of dest
+        RangeMapping::from_len(src, src, len)?;
+        let mapping = Self {
+            src,
+            dest,
+            len,
+            scale,
+        };
+        if scaled(dest, scale, T::widen_len(len) - 1).is_none() {
+            return Err(RangeError::ScaledOverflow {
+                dest,
+                len,
+                scale: scale.get(),
+            });
+        }
+        Ok(mapping.normalized())
+    }
+
+    /// scale 1 for single ids
+    fn normalized(self) -> Self {
+        if self.len == 1.into() {
+            Self { scale: ONE, ..self }
+        } else {
+            self
+        }
+    }
+
+    /// the checks of [Self::new] for a piece that was deserialized
+    pub(super) fn validate(&self) -> Result<(), RangeError<T>> {
+        Self::new(self.src, self.dest, self.len, self.scale).map(drop)
+    }
+
+    fn last(&self) -> T {
+        T::narrow(T::widen(self.src_end_excl()) - 1).expect("the range is not empty")
+    }
+}
+
+impl<T: WideId> Piece<T> for AffineMapping<T> {
+    fn src(&self) -> T {
+        self.src
+    }
+
+    fn src_end_excl(&self) -> T {
+        self.src
+            .checked_add_len(self.len)
+            .expect("constructor guarantees src range to fit the id type")
+    }
+
+    fn map_point(&self, id: T) -> T {
+        debug_assert!(self.src <= id && id < self.src_end_excl());
+        scaled(self.dest, self.scale, T::widen(id) - T::widen(self.src))
+            .expect("constructor guarantees all dest values to fit the id type")
+    }
+
+    fn dflt_from_bounds(src: T, src_end_excl: T) -> Result<Self, RangeError<T>> {
+        RangeMapping::dflt_from_bounds(src, src_end_excl).map(Self::from)
+    }
+
+    fn restrict_src(&self, start: T, end_excl: T) -> Option<Self> {
+        let start = start.max(self.src);
+        let end_excl = end_excl.min(self.src_end_excl());
+        (start < end_excl).then(|| {
+            Self {
+                src: start,
+                dest: self.map_point(start),
+                len: start.len_to(end_excl),
+                scale: self.scale,
+            }
+            .normalized()
+        })
+    }
+
+    fn merge_with_overlap(&self, other: &Self) -> Option<Self> {
+        let scale = self.scale.get();
+        // steps t from self.src with other.src <= dest + scale * t < other.src_end_excl
+        let low = T::widen(other.src) - T::widen(self.dest);
+        let high = T::widen(other.src_end_excl()) - 1 - T::widen(self.dest);
+        let (first, last) = if scale > 0 {
+            (div_ceil(low, scale), div_floor(high, scale))
+        } else {
+            (div_ceil(high, scale), div_floor(low, scale))
+        };
+        let first = first.max(0);
+        let last = last.min(T::widen_len(self.len) - 1);
+        if first > last {
+            return None;
+        }
+
+        let src = T::narrow(T::widen(self.src) + first).expect("part of the src range");
+        let len = T::narrow_len(last - first + 1).expect("part of the src range");
+        let scale = if first == last {
+            ONE
+        } else {
+            // two ids both pieces map to values of `T`, so this is below its width
+            self.scale
+                .checked_mul(other.scale)
+                .expect("scales of pieces with more than one id are below the width of T")
+        };
+        Some(Self {
+            src,
+            dest: other.map_point(self.map_point(src)),
+            len,
+            scale,
+        })
+    }
+
+    fn extend(&self, next: &Self) -> Option<Self> {
+        // a single id takes the scale of its neighbour
+        let scale = if self.len == 1.into() {
+            next.scale
+        } else {
+            self.scale
+        };
+        let continues = self.src_end_excl() == next.src
+            && (next.len == 1.into() || next.scale == scale)
+            && scaled(self.dest, scale, T::widen_len(self.len)) == Some(next.dest);
+        continues.then(|| {
+            let mut extended = Self { scale, ..*self };
+            extended.len += next.len;
+            extended
+        })
+    }
+
+    fn fits_into(&self, domain: Domain<T>) -> bool {
+        [
+            self.src,
+            self.last(),
+            self.dest,
+            self.map_point(self.last()),
+        ]
+        .into_iter()
+        .all(|id| domain.contains(id))
+    }
+
+    fn lowest_dest(&self, range: &Range<T>) -> Option<T> {
+        let part = self.restrict_src(range.start, range.end)?;
+        // the lowest value is at either end of the range
+        Some(if part.scale.get() > 0 {
+            part.dest
+        } else {
+            part.map_point(part.last())
+        })
+    }
+
+    fn as_offset(&self) -> Option<RangeMapping<T>> {
+        (self.scale == ONE).then_some(RangeMapping {
+            src: self.src,
+            dest: self.dest,
+            len: self.len,
+        })
+    }
+}
+
+/// `dest src len` like a [RangeMapping], optionally followed by the `scale`
+impl<T: WideId> FromStr for AffineMapping<T> {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (offset_only, scale) = match s.split_whitespace().collect_vec()[..] {
+            [dest, src, len] => (format!("{dest} {src} {len}"), "1"),
+            [dest, src, len, scale] => (format!("{dest} {src} {len}"), scale),
+            _ => return Err(format!("expected 3 or 4 numbers but got '{s}'")),
+        };
+        let mapping: RangeMapping<T> = offset_only.parse()?;
+        let scale = scale
+            .parse::<NonZeroI128>()
+            .map_err(|e| format!("invalid scale in '{s}': {e}"))?;
+        Self::new(mapping.src, mapping.dest, mapping.len, scale)
+            .map_err(|e| format!("invalid range '{s}': {e}"))
+    }
+}
+
+impl<T: WideId> From<RangeMapping<T>> for AffineMapping<T> {
+    fn from(mapping: RangeMapping<T>) -> Self {
+        Self {
+            src: mapping.src,
+            dest: mapping.dest,
+            len: mapping.len,
+            scale: ONE,
+        }
+        .normalized()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{marker::PhantomData, num::NonZeroI128};
+
+    use proptest::prelude::*;
+
+    use crate::day05::{Domain, Id, Map, Piece, RangeError, RangeMap, RangeMapping, RangeSet};
+
+    use super::{AffineMap, AffineMapping};
+
+    fn scale(scale: i128) -> NonZeroI128 {
+        NonZeroI128::new(scale).unwrap()
+    }
+
+    fn small() -> Domain<Id> {
+        Domain::new(-50, 150).unwrap()
+    }
+
+    /// the map applied to a single id, gaps map to themselves
+    fn naive(mappings: &[(Id, Id, u64, i128)], id: Id) -> Id {
+        mappings
+            .iter()
+            .find(|(src, _, len, _)| (*src..*src + *len as Id).contains(&id))
+            .map_or(id, |(src, dest, _, scale)| dest + *scale as Id * (id - src))
+    }
+
+    fn build_on(mappings: &[(Id, Id, u64, i128)], domain: Domain<Id>) -> AffineMap<Id> {
+        AffineMap::from_fill_gaps(
+            mappings
+                .iter()
+                .map(|&(src, dest, len, s)| AffineMapping::new(src, dest, len, scale(s)).unwrap()),
+            domain,
+        )
+        .unwrap()
+    }
+
+    fn build(mappings: &[(Id, Id, u64, i128)]) -> AffineMap<Id> {
+        build_on(mappings, small())
+    }
+
+    #[test]
+    fn mirror_and_stretch() {
+        // 0..10 mirrored onto 9..=0, 20..25 stretched onto 100, 102, .., 108
+        let first = [(0, 9, 10, -1), (20, 100, 5, 2)];
+        // 100..110 mirrored and stretched onto -10, -13, .., -37
+        let second = [(100, -10, 10, -3), (5, 40, 3, 1)];
+        let composed = build(&second).compose(&build(&first));
+        for id in -50..150 {
+            assert_eq!(
+                composed.get(id),
+                Ok(naive(&second, naive(&first, id))),
+                "at {id}"
+            );
+        }
+        assert_eq!(composed.get(150), Err(RangeError::Unmapped { id: 150 }));
+        assert_eq!(composed.get(22), Ok(-22));
+        assert_eq!(composed.to_offset_only(), None);
+        // still sorted by src, even though the mirrored pieces are joined backwards
+        assert!(composed.is_total(small()));
+
+        // 20..25 ends up at -10, -16, .., -34
+        let seeds = RangeSet::new([20..23, 0..3]);
+        assert_eq!(composed.lowest_dest(&seeds), Some(-22));
+        assert_eq!(composed.lowest_dest(&RangeSet::new([0..5, 5..10])), Some(0));
+        assert_eq!(composed.lowest_dest(&RangeSet::default()), None);
+    }
+
+    #[test]
+    fn parse_and_validate() {
+        assert_eq!(
+            "9 0 10 -1".parse::<AffineMapping<Id>>(),
+            Ok(AffineMapping::new(0, 9, 10, scale(-1)).unwrap())
+        );
+        assert_eq!(
+            "9 0 10".parse::<AffineMapping<Id>>(),
+            Ok(RangeMapping::from_len(0, 9, 10).unwrap().into())
+        );
+        assert!("9 0 10 0".parse::<AffineMapping<Id>>().is_err());
+        assert!("9 0".parse::<AffineMapping<Id>>().is_err());
+        // 0..10 stretched would need values up to 18 + u32::MAX - 9
+        assert_eq!(
+            AffineMapping::<u32>::new(0, u32::MAX - 9, 10, scale(2)),
+            Err(RangeError::ScaledOverflow {
+                dest: u32::MAX - 9,
+                len: 10,
+                scale: 2
+            })
+        );
+        assert!(AffineMapping::<u32>::new(0, 9, 10, scale(-2)).is_err());
+        assert!(AffineMapping::<u32>::new(0, 9, 2, scale(i128::MAX)).is_err());
+        // mirrored out of the domain
+        assert_eq!(
+            AffineMap::from_fill_gaps(
+                [AffineMapping::new(0, -45, 10, scale(-1)).unwrap()].into_iter(),
+                small()
+            ),
+            Err(RangeError::OutOfDomain {
+                src: 0,
+                src_end_excl: 10,
+                domain: small()
+            })
+        );
+
+        // the same section parsing and overlap checks as offset-only lines
+        let section = "seed-to-soil map:\n-5 0 10 -1\n20 40 2";
+        let map: AffineMap<Id> = Map::parse_section(section, 3).unwrap().try_into().unwrap();
+        assert_eq!(map.get(3), Ok(-8));
+        assert_eq!(map.get(41), Ok(21));
+        assert_eq!(
+            Map::<Id, AffineMapping<Id>>::parse_section("x-to-y map:\n1 2 3\n1 2", 7).err(),
+            Some("line 9: expected 3 or 4 numbers but got '1 2'".to_string())
+        );
+        let overlapping =
+            Map::<Id, AffineMapping<Id>>::parse_section("x-to-y map:\n0 0 10 -1\n50 5 2 3", 7)
+                .unwrap();
+        assert_eq!(overlapping.overlaps()[0].second_line_no, 9);
+    }
+
+    #[test]
+    fn offset_only_specialization() {
+        let first = RangeMap::<Id>::from_fill_gaps(
+            [
+                RangeMapping::from_len(0, 10, 5).unwrap(),
+                RangeMapping::from_len(10, 0, 5).unwrap(),
+            ]
+            .into_iter(),
+            Domain::full(),
+        )
+        .unwrap();
+        let second = RangeMap::from_fill_gaps(
+            [RangeMapping::from_len(3, 12, 9).unwrap()].into_iter(),
+            Domain::full(),
+        )
+        .unwrap();
+        let scalable = |map: &RangeMap<Id>| -> AffineMap<Id> {
+            RangeMap(
+                map.0.iter().copied().map(AffineMapping::from).collect(),
+                PhantomData,
+            )
+        };
+
+        let composed = scalable(&second).compose(&scalable(&first));
+        assert_eq!(composed.to_offset_only(), Some(second.compose(&first)));
+
+        // mirroring twice is the identity
+        let mirror = build(&[(0, 9, 10, -1)]);
+        assert_eq!(
+            mirror.compose(&mirror).to_offset_only(),
+            Some(RangeMap::identity(small()))
+        );
+    }
+
+    #[test]
+    fn scales_beyond_i64() {
+        // the scale of a single id is dropped, no matter how steep
+        let steep = build(&[(0, 0, 1, i64::MAX.into())]);
+        assert_eq!(steep.compose(&build(&[(0, 0, 1, 2)])).get(0), Ok(0));
+
+        // 0 -> 0, 1 -> 2^32, which the second one maps to i64::MIN and 0
+        let first = build_on(&[(0, 0, 2, 1 << 32)], Domain::full());
+        let second = build_on(&[(0, Id::MIN, (1 << 32) + 1, 1 << 31)], Domain::full());
+        let composed = second.compose(&first);
+        assert_eq!(composed.get(0), Ok(Id::MIN));
+        assert_eq!(composed.get(1), Ok(0));
+        assert!(composed
+            .0
+            .iter()
+            .any(|piece| piece.src() == 0 && piece.len == 2));
+    }
+
+    proptest! {
+        #[test]
+        fn compose_matches_naive(
+            first in prop::collection::vec((0..5 as Id, 1..8u64, -20..20 as Id, -3..=3i128), 0..4),
+            second in prop::collection::vec((0..5 as Id, 1..8u64, -20..20 as Id, -3..=3i128), 0..4),
+        ) {
+            // non-overlapping pieces starting at -20, zero scales are skipped
+            let layout = |pieces: Vec<(Id, u64, Id, i128)>| {
+                let mut src = -20;
+                pieces
+                    .into_iter()
+                    .filter(|piece| piece.3 != 0)
+                    .map(|(gap, len, dest, scale)| {
+                        src += gap;
+                        let mapping = (src, dest, len, scale);
+                        src += len as Id;
+                        mapping
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let (first, second) = (layout(first), layout(second));
+            let composed = build(&second).compose(&build(&first));
+            prop_assert!(composed.is_total(small()));
+            for id in -50..150 {
+                prop_assert_eq!(composed.get(id), Ok(naive(&second, naive(&first, id))), "at {}", id);
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::{
+    iter::successors,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use rayon::prelude::*;
+
+use super::{Piece, RangeId, RangeMap, SeedRange, Total};
+
+/// amount of seeds a single rayon task maps
+const CHUNK_LEN: u32 = 1 << 20;
+
+/// Reference solver that doesn't rely on any of the range algebra:
+/// every single seed of every seed range is looked up in every stage of
+/// `chain` one after another, split across threads with rayon
+/// Progress is reported to stderr in steps of 10%
+///
+/// [None] if there are no seeds
+pub(super) fn lowest_location<T: RangeId, P: Piece<T>>(
+    seed_ranges: &[SeedRange<T>],
+    chain: &[&RangeMap<T, Total, P>],
+) -> Option<T> {
+    let chunks = seed_ranges
+        .iter()
+        .flat_map(|seed_range| {
+            successors(Some(seed_range.start), |start| {
+                start.checked_add_len(CHUNK_LEN.into())
+            })
+            .take_while(|start| *start < seed_range.end)
+            .map(|start| {
+                let end = start
+                    .checked_add_len(CHUNK_LEN.into())
+                    .map_or(seed_range.end, |end| end.min(seed_range.end));
+                start..end
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let done = AtomicUsize::new(0);
+    let report_every = chunks.len().div_ceil(10).max(1);
+
+    chunks
+        .par_iter()
+        .map(|chunk| {
+            let lowest = successors(Some(chunk.start), |seed| seed.checked_add_len(1.into()))
+                .take_while(|seed| *seed < chunk.end)
+                .map(|seed| {
+                    chain.iter().fold(seed, |id, map| {
+                        map.get(id).expect("ids below MAX stay mapped")
+                    })
+                })
+                .min();
+
+            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+            if done.is_multiple_of(report_every) || done == chunks.len() {
+                eprintln!(
+                    "brute force: {done}/{} chunks ({}%)",
+                    chunks.len(),
+                    done * 100 / chunks.len()
+                );
+            }
+            lowest
+        })
+        .flatten()
+        .min()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::day05::{test::example_chain, Id, RangeSet};
+
+    use super::lowest_location;
+
+    #[test]
+    fn matches_image() {
+        let seeds = vec![79..93, 55..68];
+        let maps = example_chain();
+        let chain = maps.iter().collect::<Vec<_>>();
+
+        let expected = chain
+            .iter()
+            .fold(RangeSet::new(seeds.clone()), |set, map| map.image(&set))
+            .min();
+
+        assert_eq!(lowest_location(&seeds, &chain), expected);
+        // the answer to part 2 of the example
+        assert_eq!(expected, Some(46));
+        assert_eq!(lowest_location::<Id, _>(&[], &chain), None);
+    }
+}
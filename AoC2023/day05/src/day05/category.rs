@@ -0,0 +1,234 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+/// splits a `X-to-Y map:` section header into its source and destination category
+pub(super) fn parse_header(line: &str) -> Result<(&str, &str), String> {
+    line.trim()
+        .strip_suffix(" map:")
+        .and_then(|name| name.split_once("-to-"))
+        .filter(|(src, dest)| !src.is_empty() && !dest.is_empty())
+        .ok_or_else(|| format!("expected a `X-to-Y map:` header, got {line:?}"))
+}
+
+/// A single almanac section, converting ids of category `src` to category `dest`
+/// `M` is the kind of map the section was parsed into (usually a [super::RangeMap])
+#[derive(Debug)]
+pub(super) struct Conversion<'a, M> {
+    pub(super) src: &'a str,
+    pub(super) dest: &'a str,
+    /// `X-to-Y` as in the section header
+    pub(super) name: String,
+    pub(super) map: M,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum PathError {
+    UnknownCategory(String),
+    Missing {
+        from: String,
+        to: String,
+    },
+    /// the categories of every path that was found
+    Ambiguous {
+        from: String,
+        to: String,
+        paths: Vec<Vec<String>>,
+    },
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathError::UnknownCategory(category) => {
+                write!(f, "no map converts from or to category {category:?}")
+            }
+            PathError::Missing { from, to } => {
+                write!(f, "no chain of maps converts {from:?} to {to:?}")
+            }
+            PathError::Ambiguous { from, to, paths } => {
+                writeln!(
+                    f,
+                    "{} chains of maps convert {from:?} to {to:?}:",
+                    paths.len()
+                )?;
+                for path in paths {
+                    writeln!(f, "  {}", path.join(" -> "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for PathError {}
+
+/// Categories as nodes and almanac sections as the directed edges between them
+/// Allows converting between any two categories instead of hardcoding the
+/// seed -> ... -> location order
+#[derive(Debug)]
+pub(super) struct CategoryGraph<'a, M>(Vec<Conversion<'a, M>>);
+
+impl<'a, M> CategoryGraph<'a, M> {
+    pub(super) fn new() -> Self {
+        Self(vec![])
+    }
+
+    pub(super) fn add(&mut self, src: &'a str, dest: &'a str, map: M) {
+        self.0.push(Conversion {
+            src,
+            dest,
+            name: format!("{src}-to-{dest}"),
+            map,
+        });
+    }
+
+    fn knows(&self, category: &str) -> bool {
+        self.0
+            .iter()
+            .any(|conversion| conversion.src == category || conversion.dest == category)
+    }
+
+    /// The conversions leading from `from` to `to`, in the order they have to be applied
+    /// An error if there is no such chain or more than one of them (without
+    /// visiting a category twice), as the result would depend on which one is taken
+    pub(super) fn path(&self, from: &str, to: &str) -> Result<Vec<&Conversion<'a, M>>, PathError> {
+        for category in [from, to] {
+            if !self.knows(category) {
+                return Err(PathError::UnknownCategory(category.to_string()));
+            }
+        }
+
+        let mut found = vec![];
+        let mut current = vec![];
+        self.collect_paths(from, to, &mut current, &mut found);
+
+        match found.len() {
+            0 => Err(PathError::Missing {
+                from: from.to_string(),
+                to: to.to_string(),
+            }),
+            1 => Ok(found.pop().expect("length was checked")),
+            _ => Err(PathError::Ambiguous {
+                from: from.to_string(),
+                to: to.to_string(),
+                paths: found
+                    .iter()
+                    .map(|path| {
+                        [from.to_string()]
+                            .into_iter()
+                            .chain(path.iter().map(|conversion| conversion.dest.to_string()))
+                            .collect()
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    /// depth first search for all simple paths, the graph is tiny
+    fn collect_paths<'g>(
+        &'g self,
+        at: &str,
+        to: &str,
+        current: &mut Vec<&'g Conversion<'a, M>>,
+        found: &mut Vec<Vec<&'g Conversion<'a, M>>>,
+    ) {
+        if at == to {
+            found.push(current.clone());
+            return;
+        }
+        for conversion in self.0.iter().filter(|conversion| conversion.src == at) {
+            let start = current.first().map_or(at, |first| first.src);
+            let revisits = conversion.dest == start
+                || current.iter().any(|taken| taken.dest == conversion.dest);
+            if revisits {
+                continue;
+            }
+            current.push(conversion);
+            self.collect_paths(conversion.dest, to, current, found);
+            current.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::day05::{Domain, Id, RangeMap, RangeMapping};
+
+    use super::{parse_header, CategoryGraph, PathError};
+
+    fn shift(by: Id) -> RangeMap<Id> {
+        RangeMap::from_fill_gaps(
+            [RangeMapping::from_len(0, by, 10).unwrap()].into_iter(),
+            Domain::full(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(parse_header("seed-to-soil map:"), Ok(("seed", "soil")));
+        assert_eq!(
+            parse_header("temperature-to-humidity map:\n"),
+            Ok(("temperature", "humidity"))
+        );
+        assert!(parse_header("seed-to-soil").is_err());
+        assert!(parse_header("seeds: 1 2").is_err());
+        assert!(parse_header("-to-soil map:").is_err());
+    }
+
+    #[test]
+    fn resolve_paths() {
+        let mut graph = CategoryGraph::new();
+        // deliberately not in chain order
+        graph.add("water", "light", shift(100));
+        graph.add("seed", "soil", shift(1));
+        graph.add("soil", "water", shift(10));
+        graph.add("fertilizer", "light", shift(0));
+
+        let path = graph.path("seed", "light").unwrap();
+        assert_eq!(
+            path.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(),
+            vec!["seed-to-soil", "soil-to-water", "water-to-light"]
+        );
+        // 0 -> 1 -> 11 -> 11, the last shift only applies to 0..10
+        assert_eq!(
+            path.iter()
+                .fold(0, |id, conversion| conversion.map.get(id).unwrap()),
+            11
+        );
+        // converting a category to itself takes no steps
+        assert!(graph.path("soil", "soil").unwrap().is_empty());
+
+        assert_eq!(
+            graph.path("light", "seed").unwrap_err(),
+            PathError::Missing {
+                from: "light".to_string(),
+                to: "seed".to_string()
+            }
+        );
+        assert_eq!(
+            graph.path("seed", "humidity").unwrap_err(),
+            PathError::UnknownCategory("humidity".to_string())
+        );
+
+        graph.add("seed", "fertilizer", shift(0));
+        assert_eq!(
+            graph.path("seed", "light").unwrap_err(),
+            PathError::Ambiguous {
+                from: "seed".to_string(),
+                to: "light".to_string(),
+                paths: vec![
+                    vec!["seed", "soil", "water", "light"],
+                    vec!["seed", "fertilizer", "light"]
+                ]
+                .into_iter()
+                .map(|path| path.into_iter().map(String::from).collect())
+                .collect()
+            }
+        );
+        // the detour doesn't matter for categories before it
+        assert!(graph.path("soil", "light").is_ok());
+    }
+}
@@ -0,0 +1,348 @@
+use std::fmt::Write;
+
+use serde_json::{json, Value};
+
+use super::{Piece, RangeId, RangeMap};
+
+const WIDTH: f64 = 1200.0;
+const LABEL_WIDTH: f64 = 220.0;
+const PLOT_WIDTH: f64 = WIDTH - LABEL_WIDTH - 20.0;
+const BAR_HEIGHT: f64 = 12.0;
+/// vertical distance between the `src` and the `dest` bars of a stage
+const LINK_HEIGHT: f64 = 80.0;
+const STAGE_HEIGHT: f64 = LINK_HEIGHT + BAR_HEIGHT + 40.0;
+
+/// pieces that map to themselves are drawn in grey
+const IDENTITY_COLOR: &str = "#ced4da";
+/// the colors excalidraw offers by default
+const PALETTE: [&str; 6] = [
+    "#a5d8ff", "#ffc9c9", "#b2f2bb", "#ffec99", "#d0bfff", "#ffd8a8",
+];
+
+/// `text` with the characters that have a meaning in XML replaced by entities
+fn xml_escape(text: &str) -> String {
+    text.chars()
+        .fold(String::with_capacity(text.len()), |mut escaped, c| {
+            match c {
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '&' => escaped.push_str("&amp;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&apos;"),
+                c => escaped.push(c),
+            }
+            escaped
+        })
+}
+
+/// a horizontal bar representing a `src` or `dest` range
+struct Bar {
+    x: f64,
+    y: f64,
+    width: f64,
+    color: &'static str,
+}
+
+/// connects the lower edge of a `src` [Bar] with the upper edge of its `dest` [Bar]
+struct Link {
+    src: (f64, f64),
+    dest: (f64, f64),
+    src_y: f64,
+    dest_y: f64,
+    color: &'static str,
+}
+
+struct Label {
+    x: f64,
+    y: f64,
+    text: String,
+}
+
+/// Laid out drawing of a chain of [RangeMap]s, every stage is drawn as a row
+/// of `src` bars above a row of `dest` bars with links between them
+/// Replaces the hand drawn `doc/aoc2023_day05.excalidraw`
+pub(super) struct Diagram {
+    height: f64,
+    bars: Vec<Bar>,
+    links: Vec<Link>,
+    labels: Vec<Label>,
+}
+
+impl Diagram {
+    /// A single map is just a chain of length one
+    ///
+    /// All stages share the same horizontal scale which covers every bound
+    /// except [RangeId::MIN] and [RangeId::MAX], ranges reaching down/up to
+    /// them start at the left/end at the right edge
+    pub(super) fn from_chain<T: RangeId>(stages: &[(&str, &RangeMap<T>)]) -> Self {
+        let bounds = stages
+            .iter()
+            .flat_map(|(_, map)| map.0.iter())
+            .flat_map(|mapping| {
+                [
+                    mapping.src,
+                    mapping.src_end_excl(),
+                    mapping.dest,
+                    mapping.dest_end_excl(),
+                ]
+            })
+            .filter(|bound| *bound != T::MIN && *bound != T::MAX)
+            .map(T::as_f64);
+        let (view_start, view_end) = bounds.fold((f64::MAX, f64::MIN), |(lo, hi), bound| {
+            (lo.min(bound), hi.max(bound))
+        });
+        let (view_start, view_end) = if view_start < view_end {
+            (view_start, view_end)
+        } else {
+            // nothing but identity up to MAX
+            (0.0, 1.0)
+        };
+        let x = |id: T| {
+            let clamped = id.as_f64().clamp(view_start, view_end);
+            LABEL_WIDTH + (clamped - view_start) / (view_end - view_start) * PLOT_WIDTH
+        };
+
+        let mut diagram = Self {
+            height: stages.len() as f64 * STAGE_HEIGHT,
+            bars: vec![],
+            links: vec![],
+            labels: vec![],
+        };
+        for (stage_idx, (name, map)) in stages.iter().enumerate() {
+            let src_y = stage_idx as f64 * STAGE_HEIGHT + 20.0;
+            let dest_y = src_y + BAR_HEIGHT + LINK_HEIGHT;
+            diagram.labels.push(Label {
+                x: 10.0,
+                y: src_y + (BAR_HEIGHT + LINK_HEIGHT) / 2.0,
+                text: name.to_string(),
+            });
+
+            for (piece_idx, mapping) in map.0.iter().enumerate() {
+                let color = if mapping.src == mapping.dest {
+                    IDENTITY_COLOR
+                } else {
+                    PALETTE[piece_idx % PALETTE.len()]
+                };
+                let src = (x(mapping.src), x(mapping.src_end_excl()));
+                let dest = (x(mapping.dest), x(mapping.dest_end_excl()));
+
+                for ((start, end), y) in [(src, src_y), (dest, dest_y)] {
+                    diagram.bars.push(Bar {
+                        x: start,
+                        y,
+                        width: end - start,
+                        color,
+                    });
+                }
+                diagram.links.push(Link {
+                    src,
+                    dest,
+                    src_y: src_y + BAR_HEIGHT,
+                    dest_y,
+                    color,
+                });
+            }
+        }
+        diagram
+    }
+
+    pub(super) fn to_svg(&self) -> String {
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{}" font-family="monospace" font-size="14">"#,
+            self.height
+        )
+        .expect("writing to a String can't fail");
+        for link in &self.links {
+            writeln!(
+                svg,
+                r#"  <polygon points="{:.1},{:.1} {:.1},{:.1} {:.1},{:.1} {:.1},{:.1}" fill="{}" fill-opacity="0.4" stroke="{}"/>"#,
+                link.src.0,
+                link.src_y,
+                link.src.1,
+                link.src_y,
+                link.dest.1,
+                link.dest_y,
+                link.dest.0,
+                link.dest_y,
+                link.color,
+                link.color,
+            )
+            .expect("writing to a String can't fail");
+        }
+        for bar in &self.bars {
+            writeln!(
+                svg,
+                r##"  <rect x="{:.1}" y="{:.1}" width="{:.1}" height="{BAR_HEIGHT}" fill="{}" stroke="#1e1e1e" stroke-width="0.5"/>"##,
+                bar.x, bar.y, bar.width, bar.color,
+            )
+            .expect("writing to a String can't fail");
+        }
+        for label in &self.labels {
+            writeln!(
+                svg,
+                r#"  <text x="{:.1}" y="{:.1}">{}</text>"#,
+                label.x,
+                label.y,
+                xml_escape(&label.text)
+            )
+            .expect("writing to a String can't fail");
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// can be opened with excalidraw.com (or the vscode extension) to annotate it further
+    pub(super) fn to_excalidraw(&self) -> String {
+        let element = |idx: usize, kind: &str, x: f64, y: f64, color: &str| {
+            json!({
+                "id": format!("{kind}-{idx}"),
+                "type": kind,
+                "x": x,
+                "y": y,
+                "angle": 0,
+                "strokeColor": "#1e1e1e",
+                "backgroundColor": color,
+                "fillStyle": "solid",
+                "strokeWidth": 1,
+                "strokeStyle": "solid",
+                "roughness": 0,
+                "opacity": 100,
+                "groupIds": [],
+                "frameId": null,
+                "roundness": null,
+                "seed": idx,
+                "version": 1,
+                "versionNonce": idx,
+                "isDeleted": false,
+                "boundElements": [],
+                "updated": 0,
+                "link": null,
+                "locked": false,
+            })
+        };
+        let with = |mut element: Value, extra: Value| {
+            let object = element.as_object_mut().expect("elements are objects");
+            object.extend(extra.as_object().expect("extras are objects").clone());
+            element
+        };
+
+        let mut elements = vec![];
+        for link in &self.links {
+            for (src_x, dest_x) in [(link.src.0, link.dest.0), (link.src.1, link.dest.1)] {
+                let line = element(elements.len(), "line", src_x, link.src_y, "transparent");
+                let (dx, dy) = (dest_x - src_x, link.dest_y - link.src_y);
+                elements.push(with(
+                    line,
+                    json!({
+                        "strokeColor": link.color,
+                        "width": dx.abs(),
+                        "height": dy,
+                        "points": [[0.0, 0.0], [dx, dy]],
+                        "lastCommittedPoint": null,
+                        "startBinding": null,
+                        "endBinding": null,
+                        "startArrowhead": null,
+                        "endArrowhead": null,
+                    }),
+                ));
+            }
+        }
+        for bar in &self.bars {
+            let rect = element(elements.len(), "rectangle", bar.x, bar.y, bar.color);
+            elements.push(with(
+                rect,
+                json!({ "width": bar.width, "height": BAR_HEIGHT }),
+            ));
+        }
+        for label in &self.labels {
+            let text = element(elements.len(), "text", label.x, label.y, "transparent");
+            elements.push(with(
+                text,
+                json!({
+                    "width": label.text.len() as f64 * 10.0,
+                    "height": 25,
+                    "text": label.text,
+                    "originalText": label.text,
+                    "fontSize": 16,
+                    "fontFamily": 3,
+                    "textAlign": "left",
+                    "verticalAlign": "top",
+                    "baseline": 14,
+                    "containerId": null,
+                    "lineHeight": 1.25,
+                }),
+            ));
+        }
+
+        let document = json!({
+            "type": "excalidraw",
+            "version": 2,
+            "source": "https://excalidraw.com",
+            "elements": elements,
+            "appState": {
+                "gridSize": null,
+                "viewBackgroundColor": "#ffffff"
+            },
+            "files": {}
+        });
+        serde_json::to_string_pretty(&document).expect("json values always serialize")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::day05::{Domain, Id, RangeMap, RangeMapping};
+
+    use super::{Diagram, LABEL_WIDTH, PLOT_WIDTH};
+
+    #[test]
+    fn export_chain() {
+        let first = RangeMap::<Id>::from_fill_gaps(
+            [RangeMapping::from_len(0, 50, 50).unwrap()].into_iter(),
+            Domain::new(0, Id::MAX).unwrap(),
+        )
+        .unwrap();
+        let second = RangeMap::from_fill_gaps(
+            [RangeMapping::from_len(50, 100, 50).unwrap()].into_iter(),
+            Domain::new(0, Id::MAX).unwrap(),
+        )
+        .unwrap();
+
+        let diagram = Diagram::from_chain(&[("first", &first), ("second", &second)]);
+        // 2 + 3 pieces, all of them with a src and dest bar
+        assert_eq!(diagram.bars.len(), 10);
+        assert_eq!(diagram.links.len(), 5);
+        // the view covers 0..150, the upper fillers end at the right edge
+        assert_eq!(diagram.bars[0].x, LABEL_WIDTH);
+        assert_eq!(diagram.bars[1].x, LABEL_WIDTH + PLOT_WIDTH / 3.0);
+        assert_eq!(
+            diagram.bars[9].x + diagram.bars[9].width,
+            LABEL_WIDTH + PLOT_WIDTH
+        );
+
+        let svg = diagram.to_svg();
+        assert_eq!(svg.matches("<rect").count(), 10);
+        assert_eq!(svg.matches("<polygon").count(), 5);
+        assert!(svg.contains(">second</text>"));
+
+        let excalidraw: serde_json::Value = serde_json::from_str(&diagram.to_excalidraw()).unwrap();
+        let elements = excalidraw["elements"].as_array().unwrap();
+        // 2 lines per link
+        assert_eq!(elements.len(), 10 + 5 * 2 + 2);
+        assert_eq!(elements.last().unwrap()["text"], "second");
+
+        // names are escaped, as XML text and as JSON string respectively
+        let name = r#"<seed> & "soil""#;
+        let diagram = Diagram::from_chain(&[(name, &first)]);
+        assert!(diagram
+            .to_svg()
+            .contains(">&lt;seed&gt; &amp; &quot;soil&quot;</text>"));
+        let excalidraw: serde_json::Value = serde_json::from_str(&diagram.to_excalidraw()).unwrap();
+        assert_eq!(
+            excalidraw["elements"].as_array().unwrap().last().unwrap()["text"],
+            name
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt::Write;
+
+use super::{Piece, RangeId, RangeMap, RangeMapping, RangeSet};
+
+/// A piece of a traced join together with where its values passed through
+/// `trail[i]` is the start of the range the piece occupied after stage `i`
+/// (`trail[0]` being the `src` itself), all of them share `mapping.len`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct TracedMapping<T: RangeId> {
+    mapping: RangeMapping<T>,
+    trail: Vec<T>,
+}
+
+/// Opt-in alternative to folding [RangeMap::image] which keeps the
+/// intermediate ranges (and the names of the stages that produced them)
+/// for every output piece
+#[derive(Clone, Debug)]
+pub(super) struct TracedRangeMap<'a, T: RangeId> {
+    stage_names: Vec<&'a str>,
+    pieces: Vec<TracedMapping<T>>,
+}
+
+impl<'a, T: RangeId> TracedRangeMap<'a, T> {
+    /// starts tracing at `start` (i.e. the seeds), `name` labels its ranges
+    pub(super) fn new(start: &RangeSet<T>, name: &'a str) -> Self {
+        Self {
+            stage_names: vec![name],
+            pieces: start
+                .ranges()
+                .iter()
+                .map(|range| TracedMapping {
+                    mapping: RangeMapping::dflt_from_bounds(range.start, range.end)
+                        .expect("set ranges are never empty"),
+                    trail: vec![range.start],
+                })
+                .collect(),
+        }
+    }
+
+    /// like [RangeMap::left_join] with `self` as the inner map
+    /// the trail of every piece gets narrowed to the part that overlaps `outer`
+    pub(super) fn left_join(&self, outer: &RangeMap<T>, stage_name: &'a str) -> Self {
+        let pieces = self
+            .pieces
+            .iter()
+            .flat_map(|inner| {
+                outer.0.iter().filter_map(|outer_range| {
+                    let merged = inner.mapping.merge_with_overlap(outer_range)?;
+                    let offset = inner.mapping.src.len_to(merged.src);
+                    let trail = inner
+                        .trail
+                        .iter()
+                        .map(|start| {
+                            start
+                                .checked_add_len(offset)
+                                .expect("narrowed range is part of the original one")
+                        })
+                        .chain([merged.dest])
+                        .collect();
+                    Some(TracedMapping {
+                        mapping: merged,
+                        trail,
+                    })
+                })
+            })
+            .collect();
+
+        let mut stage_names = self.stage_names.clone();
+        stage_names.push(stage_name);
+        Self {
+            stage_names,
+            pieces,
+        }
+    }
+
+    /// Explains end-to-end how the lowest `dest` of all pieces came to be
+    /// [None] if there are no pieces
+    pub(super) fn explain_lowest(&self) -> Option<String> {
+        self.pieces
+            .iter()
+            .min_by_key(|piece| piece.mapping.dest)
+            .map(|piece| self.explain(piece))
+    }
+
+    /// the first value of the piece is followed through every stage
+    fn explain(&self, piece: &TracedMapping<T>) -> String {
+        let name_width = self.stage_names.iter().map(|name| name.len()).max();
+        let name_width = name_width.unwrap_or_default();
+
+        let mut res = String::new();
+        for (idx, (name, start)) in self.stage_names.iter().zip(&piece.trail).enumerate() {
+            let end_excl = start
+                .checked_add_len(piece.mapping.len)
+                .expect("trail ranges have the length of the mapping");
+            let arrow = if idx == 0 { "  " } else { "->" };
+            writeln!(
+                res,
+                "{arrow} {name:<name_width$} {start} (of {start}..{end_excl})"
+            )
+            .expect("writing to a String can't fail");
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::day05::{Domain, Id, RangeMap, RangeMapping, RangeSet};
+
+    use super::TracedRangeMap;
+
+    #[test]
+    fn trace_through_stages() {
+        let seeds = RangeSet::<Id>::new([10..15, 0..2]);
+        let to_soil = RangeMap::from_fill_gaps(
+            [
+                RangeMapping::from_len(12, 100, 5).unwrap(),
+                RangeMapping::from_len(0, 50, 1).unwrap(),
+            ]
+            .into_iter(),
+            Domain::full(),
+        )
+        .unwrap();
+        let to_water = RangeMap::from_fill_gaps(
+            [RangeMapping::from_len(101, 0, 2).unwrap()].into_iter(),
+            Domain::full(),
+        )
+        .unwrap();
+
+        let traced = TracedRangeMap::new(&seeds, "seed")
+            .left_join(&to_soil, "seed-to-soil")
+            .left_join(&to_water, "soil-to-water");
+
+        assert_eq!(
+            traced
+                .pieces
+                .iter()
+                .map(|p| p.mapping.dest..p.mapping.dest_end_excl())
+                .collect::<RangeSet<_>>(),
+            to_water.image(&to_soil.image(&seeds))
+        );
+        assert_eq!(
+            traced
+                .pieces
+                .iter()
+                .map(|p| p.trail.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                vec![0, 50, 50],
+                vec![1, 1, 1],
+                vec![10, 10, 10],
+                vec![12, 100, 100],
+                vec![13, 101, 0],
+            ]
+        );
+        assert_eq!(
+            traced.explain_lowest().unwrap(),
+            "   seed          13 (of 13..15)\n\
+             -> seed-to-soil  101 (of 101..103)\n\
+             -> soil-to-water 0 (of 0..2)\n"
+        );
+    }
+}
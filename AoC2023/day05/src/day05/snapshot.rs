@@ -0,0 +1,367 @@
+use std::{
+    any::type_name,
+    error::Error,
+    fmt::{Display, Formatter},
+    fs,
+    path::Path,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    affine::{AffineMap, WideId},
+    Piece, RangeId, RangeMap, RangeMapping, RangeSet, SeedRange,
+};
+
+/// Bumped whenever the serialized layout of a payload changes,
+/// snapshots of other versions get rejected instead of misread
+const VERSION: u32 = 1;
+/// first bytes of the binary form
+const MAGIC: [u8; 4] = *b"AD05";
+
+/// Something that can be written to and read back from a snapshot
+pub(super) trait Snapshot: Serialize + DeserializeOwned {
+    type Id: RangeId;
+    /// tells the different payloads apart in the header
+    const KIND: &'static str;
+
+    /// the invariants deserializing alone can't guarantee
+    fn validate(&self) -> Result<(), String>;
+}
+
+impl<T: RangeId> Snapshot for RangeMap<T> {
+    type Id = T;
+    const KIND: &'static str = "range-map";
+
+    fn validate(&self) -> Result<(), String> {
+        for mapping in &self.0 {
+            RangeMapping::from_len(mapping.src, mapping.dest, mapping.len)
+                .map_err(|e| e.to_string())?;
+        }
+        validate_total(&self.0)
+    }
+}
+
+impl<T: WideId> Snapshot for AffineMap<T> {
+    type Id = T;
+    const KIND: &'static str = "affine-map";
+
+    fn validate(&self) -> Result<(), String> {
+        for mapping in &self.0 {
+            mapping.validate().map_err(|e| e.to_string())?;
+        }
+        validate_total(&self.0)
+    }
+}
+
+/// total maps cover their domain without holes
+fn validate_total<T: RangeId, P: Piece<T>>(pieces: &[P]) -> Result<(), String> {
+    match pieces
+        .windows(2)
+        .find(|pair| pair[0].src_end_excl() != pair[1].src())
+    {
+        Some(pair) => Err(format!(
+            "src ranges are unsorted, overlap or leave a hole at {}",
+            pair[1].src()
+        )),
+        None if pieces.is_empty() => Err("no ranges".to_string()),
+        None => Ok(()),
+    }
+}
+
+impl<T: RangeId> Snapshot for RangeSet<T> {
+    type Id = T;
+    const KIND: &'static str = "range-set";
+
+    fn validate(&self) -> Result<(), String> {
+        if RangeSet::new(self.0.iter().cloned()) != *self {
+            return Err("ranges are not normalized".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// the seed ranges exactly as listed in the almanac
+impl<T: RangeId> Snapshot for Vec<SeedRange<T>> {
+    type Id = T;
+    const KIND: &'static str = "seed-ranges";
+
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Header {
+    /// has to stay the first field, see [VERSION]
+    version: u32,
+    kind: String,
+    id_type: String,
+}
+
+impl Header {
+    fn of<P: Snapshot>() -> Self {
+        Self {
+            version: VERSION,
+            kind: P::KIND.to_string(),
+            id_type: type_name::<P::Id>().to_string(),
+        }
+    }
+
+    fn check<P: Snapshot>(self) -> Result<(), SnapshotError> {
+        let expected = Self::of::<P>();
+        if self != expected {
+            return Err(SnapshotError::Header {
+                expected,
+                found: self,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document<P> {
+    header: Header,
+    payload: P,
+}
+
+#[derive(Debug)]
+pub(super) enum SnapshotError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    /// the data doesn't start with [MAGIC]
+    NotASnapshot,
+    /// written by another version or for another payload
+    Header {
+        expected: Header,
+        found: Header,
+    },
+    /// well-formed, but breaks an invariant of the payload
+    Invalid(String),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "io: {e}"),
+            SnapshotError::Json(e) => write!(f, "json: {e}"),
+            SnapshotError::Binary(e) => write!(f, "binary: {e}"),
+            SnapshotError::NotASnapshot => write!(f, "not a snapshot (magic bytes missing)"),
+            SnapshotError::Header { expected, found } => write!(
+                f,
+                "expected {} v{} of {}, found {} v{} of {}",
+                expected.kind,
+                expected.version,
+                expected.id_type,
+                found.kind,
+                found.version,
+                found.id_type
+            ),
+            SnapshotError::Invalid(reason) => write!(f, "invalid payload: {reason}"),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Json(e)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        SnapshotError::Binary(e)
+    }
+}
+
+/// human readable (and diffable) form
+pub(super) fn to_json<P: Snapshot>(payload: &P) -> String {
+    let document = Document {
+        header: Header::of::<P>(),
+        payload,
+    };
+    serde_json::to_string_pretty(&document).expect("snapshots always serialize")
+}
+
+pub(super) fn from_json<P: Snapshot>(json: &str) -> Result<P, SnapshotError> {
+    // the header is checked before the payload is interpreted
+    let document: Document<serde_json::Value> = serde_json::from_str(json)?;
+    document.header.check::<P>()?;
+    let payload: P = serde_json::from_value(document.payload)?;
+    payload.validate().map_err(SnapshotError::Invalid)?;
+    Ok(payload)
+}
+
+/// [MAGIC], followed by the bincode encoded header and payload
+pub(super) fn to_binary<P: Snapshot>(payload: &P) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bincode::serialize_into(&mut bytes, &Header::of::<P>()).expect("headers always serialize");
+    bincode::serialize_into(&mut bytes, payload).expect("snapshots always serialize");
+    bytes
+}
+
+pub(super) fn from_binary<P: Snapshot>(bytes: &[u8]) -> Result<P, SnapshotError> {
+    let mut reader = bytes
+        .strip_prefix(&MAGIC)
+        .ok_or(SnapshotError::NotASnapshot)?;
+    let header: Header = bincode::deserialize_from(&mut reader)?;
+    header.check::<P>()?;
+    let payload: P = bincode::deserialize_from(&mut reader)?;
+    payload.validate().map_err(SnapshotError::Invalid)?;
+    Ok(payload)
+}
+
+/// `.json` files are written as json, everything else in the binary form
+pub(super) fn save<P: Snapshot>(path: &Path, payload: &P) -> Result<(), SnapshotError> {
+    if path.extension().is_some_and(|ext| ext == "json") {
+        fs::write(path, to_json(payload))?;
+    } else {
+        fs::write(path, to_binary(payload))?;
+    }
+    Ok(())
+}
+
+/// counterpart of [save]
+pub(super) fn load<P: Snapshot>(path: &Path) -> Result<P, SnapshotError> {
+    if path.extension().is_some_and(|ext| ext == "json") {
+        from_json(&fs::read_to_string(path)?)
+    } else {
+        from_binary(&fs::read(path)?)
+    }
+}
+
+/// FNV-1a over all `parts`, stable across runs and platforms (unlike
+/// [std::hash::DefaultHasher]) so it can name cache files
+pub(super) fn input_hash(parts: &[&str]) -> u64 {
+    parts
+        .iter()
+        // separate the parts so ("ab", "c") and ("a", "bc") differ
+        .flat_map(|part| part.bytes().chain([0xff]))
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Loads the payload cached in `dir` under `key`, or computes and caches it
+/// A broken cache is reported and overwritten, it never fails the computation
+pub(super) fn cached<P: Snapshot>(dir: &Path, key: u64, compute: impl FnOnce() -> P) -> P {
+    let path = dir.join(format!("{}-{key:016x}.bin", P::KIND));
+    match load(&path) {
+        Ok(payload) => return payload,
+        Err(SnapshotError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => eprintln!("ignoring cache {}: {e}", path.display()),
+    }
+
+    let payload = compute();
+    if let Err(e) = fs::create_dir_all(dir)
+        .map_err(SnapshotError::from)
+        .and_then(|_| save(&path, &payload))
+    {
+        eprintln!("failed to write cache {}: {e}", path.display());
+    }
+    payload
+}
+
+#[cfg(test)]
+mod test {
+    use crate::day05::{test::example_chain, Id, RangeMap, RangeSet, SeedRange};
+
+    use super::{
+        cached, from_binary, from_json, input_hash, load, save, to_binary, to_json, SnapshotError,
+    };
+
+    /// seed-to-soil and soil-to-fertilizer of the example
+    fn composed_chain() -> RangeMap<Id> {
+        let chain = example_chain();
+        chain[1].compose(&chain[0])
+    }
+
+    #[test]
+    fn golden_json() {
+        let golden = include_str!("testdata/composed_chain.json");
+        assert_eq!(from_json::<RangeMap<Id>>(golden).unwrap(), composed_chain());
+        // the layout itself is pinned, changing it requires bumping the version
+        assert_eq!(to_json(&composed_chain()), golden.trim_end());
+    }
+
+    #[test]
+    fn roundtrips() {
+        let map = composed_chain();
+        assert_eq!(from_binary::<RangeMap<Id>>(&to_binary(&map)).unwrap(), map);
+
+        let set = RangeSet::<u32>::new([5..8, 0..2]);
+        assert_eq!(from_json::<RangeSet<u32>>(&to_json(&set)).unwrap(), set);
+        assert_eq!(from_binary::<RangeSet<u32>>(&to_binary(&set)).unwrap(), set);
+
+        let seeds: Vec<SeedRange<u128>> = vec![10..15, 0..2, 12..20];
+        let bytes = to_binary(&seeds);
+        assert_eq!(from_binary::<Vec<SeedRange<u128>>>(&bytes).unwrap(), seeds);
+        // binary is the compact one
+        assert!(bytes.len() < to_json(&seeds).len());
+    }
+
+    #[test]
+    fn rejects_mismatches() {
+        let map = composed_chain();
+        // other payload, other id type, other version
+        assert!(matches!(
+            from_json::<RangeSet<Id>>(&to_json(&map)),
+            Err(SnapshotError::Header { .. })
+        ));
+        assert!(matches!(
+            from_binary::<RangeMap<u64>>(&to_binary(&map)),
+            Err(SnapshotError::Header { .. })
+        ));
+        let outdated = to_json(&map).replacen("\"version\": 1", "\"version\": 0", 1);
+        assert!(matches!(
+            from_json::<RangeMap<Id>>(&outdated),
+            Err(SnapshotError::Header { .. })
+        ));
+        assert!(matches!(
+            from_binary::<RangeMap<Id>>(b"{}"),
+            Err(SnapshotError::NotASnapshot)
+        ));
+        let mut truncated = to_binary(&map);
+        truncated.truncate(truncated.len() - 1);
+        assert!(matches!(
+            from_binary::<RangeMap<Id>>(&truncated),
+            Err(SnapshotError::Binary(_))
+        ));
+
+        // well-formed but not normalized
+        let unnormalized =
+            to_json(&RangeSet::<Id>::new([0..2, 5..8])).replace("\"start\": 5", "\"start\": 1");
+        assert!(matches!(
+            from_json::<RangeSet<Id>>(&unnormalized),
+            Err(SnapshotError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn cache_by_input_hash() {
+        let dir = std::env::temp_dir().join(format!("day05-snapshot-test-{}", std::process::id()));
+        let key = input_hash(&["seeds: 1 2", "location"]);
+        assert_ne!(key, input_hash(&["seeds: 1 2l", "ocation"]));
+
+        let first = cached(&dir, key, composed_chain);
+        // a hit doesn't compute anything
+        let second = cached(&dir, key, || -> RangeMap<Id> { unreachable!() });
+        assert_eq!(first, second);
+
+        let json = dir.join("map.json");
+        save(&json, &first).unwrap();
+        assert_eq!(load::<RangeMap<Id>>(&json).unwrap(), first);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
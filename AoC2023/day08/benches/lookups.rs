@@ -0,0 +1,19 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use day08::bench::Walkers;
+
+const STEPS: usize = 10_000;
+
+/// the dense table of `Crossings::go` against the binary search of `Crossings::go_sorted`
+fn lookups(c: &mut Criterion) {
+    let walkers = Walkers::from_input().expect("the input should parse");
+    let mut group = c.benchmark_group("lookups");
+    group.throughput(Throughput::Elements((STEPS * walkers.len()) as u64));
+    group.bench_function("dense table", |b| b.iter(|| walkers.dense(STEPS).unwrap()));
+    group.bench_function("binary search", |b| {
+        b.iter(|| walkers.sorted(STEPS).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookups);
+criterion_main!(benches);
@@ -1,9 +1,27 @@
-use std::{fmt::Display, str::Lines};
-
-use anyhow::{anyhow, Context, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::{Display, Formatter},
+    fs::File,
+    io::BufWriter,
+    str::Lines,
+};
+
+use anyhow::{anyhow, ensure, Context, Result};
 use itertools::Itertools;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg(feature = "bench")]
+pub mod bench;
+mod dot;
+mod jump;
+mod labels;
+mod reach;
+mod schedule;
+mod trace;
+
+use labels::{LabelRef, Labels, Location, Matcher};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum Direction {
     Left,
     Right,
@@ -21,13 +39,13 @@ impl TryFrom<char> for Direction {
 }
 
 struct Path(Vec<Direction>);
-impl IntoIterator for Path {
+impl<'a> IntoIterator for &'a Path {
     type Item = Direction;
-    type IntoIter = PathIter;
+    type IntoIter = PathIter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
         PathIter {
-            path: self.0,
+            path: &self.0,
             idx: 0,
         }
     }
@@ -41,179 +59,783 @@ fn parse_path(first_line: &str) -> Result<Path> {
         .map(Path)
 }
 
-struct PathIter {
-    path: Vec<Direction>,
+/// Repeats the path forever, unless it is empty
+struct PathIter<'a> {
+    path: &'a [Direction],
     idx: usize,
 }
-impl Iterator for PathIter {
+impl Iterator for PathIter<'_> {
     type Item = Direction;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let res = Some(self.path[self.idx]);
+        let res = *self.path.get(self.idx)?;
         self.idx = (self.idx + 1) % self.path.len();
-        res
+        Some(res)
+    }
+}
+
+struct Crossing {
+    src: Location,
+    left: Location,
+    right: Location,
+}
+impl Crossing {
+    #[cfg(any(test, feature = "bench"))]
+    fn go(&self, dir: Direction) -> Location {
+        match dir {
+            Direction::Left => self.left,
+            Direction::Right => self.right,
+        }
     }
 }
 
-/// since its only 3 letters it can A-Z can be optimized
-/// 26^3 possibilities fit into a u16, see [Location::try_from]
-/// Goal destinations (**Z) additinoally have their MSB set to one
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-struct Location(u16);
+struct Crossings {
+    labels: Labels,
+    /// sorted by [Crossing::src]
+    sorted: Vec<Crossing>,
+    /// `[left, right]` of every crossing, indexed by [Location::index]
+    /// Locations without a crossing hold [Location::NONE]
+    table: Vec<[Location; 2]>,
+}
 
-impl Location {
-    const Z_FLAG: u16 = 1 << 15;
-    /// ends with 'Z'
-    fn is_goal(&self) -> bool {
-        self.0 & Self::Z_FLAG != 0
+impl Crossings {
+    fn new(mut v: Vec<Crossing>, labels: Labels) -> Self {
+        v.sort_by_key(|c| c.src);
+        let mut table = vec![[Location::NONE; 2]; labels.len()];
+        for c in &v {
+            table[c.src.index()] = [c.left, c.right];
+        }
+        Self {
+            labels,
+            sorted: v,
+            table,
+        }
     }
 
-    /// ends with 'A'
-    fn is_start(&self) -> bool {
-        self.0 / 26u16.pow(2) == 0
+    /// the original label of `loc`
+    fn label(&self, loc: Location) -> &str {
+        self.labels.name(loc)
     }
 
-    fn as_chars(&self) -> [char; 3] {
-        let mut val = self.0 & !Self::Z_FLAG;
-        let first = val % 26;
-        val -= first;
-        let second = (val % 26u16.pow(2)) / 26;
-        val -= val % 26u16.pow(2);
-        let third = val / 26u16.pow(2);
+    /// `loc` printed as its original label
+    fn display(&self, loc: Location) -> LabelRef<'_> {
+        self.labels.display(loc)
+    }
 
-        [first, second, third].map(|u: u16| char::from_u32((u + b'A' as u16).into()).unwrap())
+    /// Resolves which locations are starts and goals
+    /// Only locations with a crossing can be starts, an error if there are
+    /// no starts or no goals at all
+    fn roles(&self, start: &Matcher, goal: &Matcher) -> Result<Roles> {
+        let roles = self.resolve_roles(start, goal);
+        let missing = roles.missing(start, goal);
+        if missing.is_empty() {
+            Ok(roles)
+        } else {
+            Err(anyhow!(missing.join(", ")))
+        }
     }
-}
 
-impl Display for Location {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.as_chars().into_iter().join(""))
+    /// [Crossings::roles] without checking that there are starts and goals
+    fn resolve_roles(&self, start: &Matcher, goal: &Matcher) -> Roles {
+        Roles {
+            starts: self
+                .sorted
+                .iter()
+                .filter(|c| start.matches(self.label(c.src)))
+                .map(|c| c.src)
+                .collect(),
+            goals: self
+                .labels
+                .iter()
+                .map(|(_, label)| goal.matches(label))
+                .collect(),
+        }
     }
-}
 
-impl TryFrom<&str> for Location {
-    type Error = anyhow::Error;
+    fn go(&self, from: Location, dir: Direction) -> Result<Location> {
+        let [left, right] = self.table[from.index()];
+        if left == Location::NONE {
+            return Err(anyhow!(
+                "unable to find {} in the crossings",
+                self.display(from)
+            ));
+        }
+        Ok(match dir {
+            Direction::Left => left,
+            Direction::Right => right,
+        })
+    }
 
-    fn try_from(value: &str) -> Result<Self, Self::Error> {
-        if value.len() == 3 && value.chars().all(|c| c.is_ascii_uppercase()) {
-            let bytes = value.as_bytes();
-            let mut res = 0u16;
-            res += (bytes[0] - b'A') as u16;
-            res += ((bytes[1] - b'A') as u16) * 26u16; // ^1
-            res += ((bytes[2] - b'A') as u16) * 26u16.pow(2);
+    /// [Crossings::go] by binary search, only kept around for comparison
+    #[cfg(any(test, feature = "bench"))]
+    fn go_sorted(&self, from: Location, dir: Direction) -> Result<Location> {
+        let crossing_idx = self
+            .sorted
+            .binary_search_by_key(&from, |c| c.src)
+            .map_err(|_| anyhow!("unable to find {} in the crossings", self.display(from)))?;
 
-            if bytes[2] == b'Z' {
-                res |= Self::Z_FLAG;
-            }
+        Ok(self.sorted[crossing_idx].go(dir))
+    }
 
-            Ok(Self(res))
-        } else {
-            Err(anyhow!("invalid location {value}"))
+    /// Walks from `from` along `path` until a `(location, path index)` state repeats
+    /// Since the next state only depends on the current one, everything from then on
+    /// is a repetition of the detected cycle
+    fn paths_to_goals(&self, from: Location, path: &Path, roles: &Roles) -> Result<GoalCycle> {
+        if path.0.is_empty() {
+            return Err(anyhow!("the path is empty, nothing ever repeats"));
         }
+
+        let mut seen = HashMap::new();
+        let mut goals = vec![];
+        let (mut loc, mut idx) = (from, 0);
+        for step in 0.. {
+            if let Some(&first_seen) = seen.get(&(loc, idx)) {
+                return Ok(GoalCycle {
+                    prefix_len: first_seen,
+                    cycle_len: step - first_seen,
+                    goals,
+                });
+            }
+            seen.insert((loc, idx), step);
+            if roles.is_goal(loc) {
+                goals.push(step);
+            }
+            loc = self.go(loc, path.0[idx])?;
+            idx = (idx + 1) % path.0.len();
+        }
+        unreachable!("there are only finitely many states")
     }
 }
 
-struct Crossing {
-    src: Location,
-    left: Location,
-    right: Location,
+/// [Matcher]s resolved against the labels of a network
+struct Roles {
+    starts: Vec<Location>,
+    /// indexed by [Location::index]
+    goals: Vec<bool>,
 }
-impl Crossing {
-    fn go(&self, dir: Direction) -> Location {
-        match dir {
-            Direction::Left => self.left,
-            Direction::Right => self.right,
+
+impl Roles {
+    fn is_goal(&self, loc: Location) -> bool {
+        self.goals[loc.index()]
+    }
+
+    /// the matchers `self` was resolved from that didn't match anything
+    fn missing(&self, start: &Matcher, goal: &Matcher) -> Vec<String> {
+        let mut missing = vec![];
+        if self.starts.is_empty() {
+            missing.push(format!("no location matches the start {start:?}"));
+        }
+        if !self.goals.contains(&true) {
+            missing.push(format!("no location matches the goal {goal:?}"));
         }
+        missing
     }
 }
 
-struct Crossings(Vec<Crossing>);
+/// Where a single walker ends up on goals
+/// After `prefix_len` steps it repeats the same `cycle_len` steps forever
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct GoalCycle {
+    prefix_len: usize,
+    cycle_len: usize,
+    /// every step count in `0..prefix_len + cycle_len` after which a goal is reached,
+    /// the ones `>= prefix_len` recur every `cycle_len` steps
+    goals: Vec<usize>,
+}
 
-impl Crossings {
-    fn new(mut v: Vec<Crossing>) -> Self {
-        v.sort_by_key(|c| c.src);
-        Self(v)
-    }
+/// A problem with a single line of the input, or with the input as a whole
+#[derive(Debug, PartialEq, Eq)]
+struct InputError {
+    /// starting at 1, [None] if no single line is to blame
+    line: Option<usize>,
+    message: String,
+}
 
-    fn starts(&self) -> Vec<Location> {
-        self.0
-            .iter()
-            .filter_map(|c| c.src.is_start().then(|| c.src))
-            .collect()
+/// Everything that is wrong with an input, reported at once
+#[derive(Debug)]
+struct InputErrors(Vec<InputError>);
+
+impl Display for InputErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{} problem(s) with the input:", self.0.len())?;
+        for error in &self.0 {
+            match error.line {
+                Some(line) => writeln!(f, "  line {line}: {}", error.message)?,
+                None => writeln!(f, "  {}", error.message)?,
+            }
+        }
+        Ok(())
     }
+}
 
-    fn go(&self, from: Location, dir: Direction) -> Result<Location> {
-        let crossing_idx = self
-            .0
-            .binary_search_by_key(&from, |c| c.src)
-            .map_err(|_| anyhow!("unable to find {from:?} in the crossings"))?;
+impl Error for InputErrors {}
+
+/// `AAA = (BBB, CCC)`
+fn parse_crossing(line: &str, labels: &mut Labels) -> Result<Crossing> {
+    let (start, lr) = line
+        .split_once(" = ")
+        .with_context(|| format!("missing ' = ' in '{line}'"))?;
+    let (l, r) = lr
+        .strip_prefix('(')
+        .and_then(|lr| lr.strip_suffix(')'))
+        .and_then(|lr| lr.split_once(", "))
+        .with_context(|| format!("expected '(LEFT, RIGHT)', got '{lr}'"))?;
+
+    Ok(Crossing {
+        src: labels.intern(start)?,
+        left: labels.intern(l)?,
+        right: labels.intern(r)?,
+    })
+}
 
-        Ok(self.0[crossing_idx].go(dir))
+/// Parses the crossings starting at line `first_line_no`
+/// Besides malformed lines, locations defined twice and crossings leading to
+/// locations that are never defined are reported
+fn parse_crossings(
+    lines: Lines<'_>,
+    first_line_no: usize,
+    errors: &mut Vec<InputError>,
+) -> Crossings {
+    let mut labels = Labels::default();
+    let mut crossings = vec![];
+    let mut defined = HashMap::new();
+    for (line_no, line) in (first_line_no..).zip(lines) {
+        let crossing = match parse_crossing(line, &mut labels) {
+            Ok(crossing) => crossing,
+            Err(err) => {
+                errors.push(InputError {
+                    line: Some(line_no),
+                    message: err.to_string(),
+                });
+                continue;
+            }
+        };
+        if let Some(first) = defined.insert(crossing.src, line_no) {
+            errors.push(InputError {
+                line: Some(line_no),
+                message: format!(
+                    "{} is already defined on line {first}",
+                    labels.name(crossing.src)
+                ),
+            });
+            defined.insert(crossing.src, first);
+            continue;
+        }
+        crossings.push((line_no, crossing));
     }
 
-    fn paths_to_goals(&self, from) {
-        
+    for (line_no, c) in &crossings {
+        for dest in [c.left, c.right].into_iter().dedup() {
+            if !defined.contains_key(&dest) {
+                errors.push(InputError {
+                    line: Some(*line_no),
+                    message: format!(
+                        "{} leads to {}, which is never defined",
+                        labels.name(c.src),
+                        labels.name(dest)
+                    ),
+                });
+            }
+        }
     }
-}
 
-fn parse_crossings(lines: Lines<'_>) -> Result<Crossings> {
-    lines
-        .map(|line| {
-            let (start, lr) = line
-                .split_once(" = ")
-                .with_context(|| format!("missing ' = ' in  '{line}'"))?;
-            let (l, r) = lr[1..lr.len() - 1]
-                .split_once(", ")
-                .with_context(|| format!("missing ', ' in  '{lr}'"))?;
+    Crossings::new(crossings.into_iter().map(|(_, c)| c).collect(), labels)
+}
 
-            Ok(Crossing {
-                src: start.try_into()?,
-                left: l.try_into()?,
-                right: r.try_into()?,
-            })
-        })
-        .try_collect()
-        .map(|it| Crossings::new(it))
+/// The path, the crossings and which of them are starts and goals, or every
+/// problem found with the input, including that no location matches `start`
+/// or `goal`
+fn parse_with_roles(
+    input: &str,
+    start: &Matcher,
+    goal: &Matcher,
+) -> Result<(Path, Crossings, Roles), InputErrors> {
+    let mut errors = vec![];
+    let (path, crossings) = parse_collecting(input, &mut errors);
+    let roles = crossings.resolve_roles(start, goal);
+    errors.extend(
+        roles
+            .missing(start, goal)
+            .into_iter()
+            .map(|message| InputError {
+                line: None,
+                message,
+            }),
+    );
+    finish((path, crossings, roles), errors)
 }
 
-const INPUT: &'static str = include_str!("input.txt");
-pub fn main() -> Result<()> {
-    let mut lines = INPUT.lines();
+/// `parsed` if there were no `errors`, which are sorted by line otherwise
+fn finish<T>(parsed: T, mut errors: Vec<InputError>) -> Result<T, InputErrors> {
+    errors.sort_by_key(|error| error.line);
+    if errors.is_empty() {
+        Ok(parsed)
+    } else {
+        Err(InputErrors(errors))
+    }
+}
 
-    let path = lines.next().context("missing first input line")?;
-    let path = parse_path(path)?;
+/// The path and the crossings, with the problems pushed to `errors`
+fn parse_collecting(input: &str, errors: &mut Vec<InputError>) -> (Path, Crossings) {
+    let mut lines = input.lines();
+
+    let path = match lines.next().map(parse_path) {
+        Some(Ok(path)) if path.0.is_empty() => {
+            errors.push(InputError {
+                line: Some(1),
+                message: "the path is empty".to_string(),
+            });
+            path
+        }
+        Some(Ok(path)) => path,
+        Some(Err(err)) => {
+            errors.push(InputError {
+                line: Some(1),
+                message: err.to_string(),
+            });
+            Path(vec![])
+        }
+        None => {
+            errors.push(InputError {
+                line: Some(1),
+                message: "missing the path".to_string(),
+            });
+            Path(vec![])
+        }
+    };
 
-    assert_eq!(lines.next(), Some(""));
+    if let Some(line) = lines.next().filter(|line| !line.is_empty()) {
+        errors.push(InputError {
+            line: Some(2),
+            message: format!("expected an empty line, got '{line}'"),
+        });
+    }
 
-    let crossings = parse_crossings(lines)?;
+    let crossings = parse_crossings(lines, 3, errors);
+    (path, crossings)
+}
 
-    let mut curr_locs = crossings.starts();
+const INPUT: &str = include_str!("input.txt");
+/// Steps needed to walk from `AAA` to exactly `ZZZ`, other goals don't count
+/// An error if the walk runs into a loop that doesn't contain `ZZZ`
+fn part1(path: &Path, crossings: &Crossings) -> Result<usize> {
+    let (start, goal) = ("AAA", "ZZZ");
+    let from = crossings.labels.get(start).context("there is no AAA")?;
+    let roles = crossings.roles(&Matcher::Exact(start.into()), &Matcher::Exact(goal.into()))?;
+
+    let mut seen = HashSet::new();
+    let mut dirs = path.into_iter();
+    let mut loc = from;
+    let mut steps = 0;
+    while !roles.is_goal(loc) {
+        if !seen.insert((loc, dirs.idx)) {
+            return Err(anyhow!(
+                "{start} never reaches {goal}, looping back to {} after {steps} steps",
+                crossings.display(loc)
+            ));
+        }
+        let dir = dirs.next().context("the path is empty")?;
+        loc = crossings.go(loc, dir)?;
+        steps += 1;
+    }
+    Ok(steps)
+}
 
-    debug_assert!(curr_locs.iter().all(|l| !l.is_goal()));
+/// Advances every walker from [Roles::starts] one step at a time until all are
+/// on goals at once, only feasible if that happens after few steps
+fn lockstep(path: &Path, crossings: &Crossings, roles: &Roles) -> Result<usize> {
+    let mut curr_locs = roles.starts.clone();
 
     let mut all_finished = true;
+    let mut error = None;
     let count = path
         .into_iter()
         .take_while_inclusive(|dir| {
-            // println!(
-            //     "{}",
-            //     curr_locs
-            //         .iter()
-            //         .map(|l| l.as_chars().iter().join(""))
-            //         .join(", ")
-            // );
             all_finished = true;
             for curr_loc in &mut curr_locs {
-                *curr_loc = crossings.go(*curr_loc, *dir).unwrap();
-                all_finished &= curr_loc.is_goal();
+                match crossings.go(*curr_loc, *dir) {
+                    Ok(next) => *curr_loc = next,
+                    Err(err) => {
+                        error = Some(err);
+                        return false;
+                    }
+                }
+                all_finished &= roles.is_goal(*curr_loc);
             }
             !all_finished
         })
         .count();
 
-    println!("steps: {count}");
+    error.map_or(Ok(count), Err)
+}
+
+pub fn main() -> Result<()> {
+    let args = std::env::args().collect_vec();
+    let matcher = |flag: &str, default: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .map_or(Some(default), |idx| args.get(idx + 1).map(String::as_str))
+            .with_context(|| format!("{flag} needs a value like suffix:A"))?
+            .parse::<Matcher>()
+    };
+    let flag_value = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|idx| args.get(idx + 1))
+            .map(String::as_str)
+    };
+    let start = matcher("--start", "suffix:A")?;
+    let goal = matcher("--goal", "suffix:Z")?;
+
+    let (path, crossings, roles) = parse_with_roles(INPUT, &start, &goal)?;
+    let jumps = jump::JumpTable::new(&crossings, &path, &roles)?;
+
+    // only needs the jump table, so it works even if part 1 or 2 fail
+    if let Some(file) = flag_value("--trace") {
+        let from = flag_value("--trace-from").map_or(Ok(0), str::parse)?;
+        let options = trace::TraceOptions {
+            every: flag_value("--trace-every").map_or(Ok(1), str::parse)?,
+            from,
+            until: flag_value("--trace-until").map_or(Ok(from.saturating_add(1000)), str::parse)?,
+            walkers: flag_value("--trace-walkers").map(str::parse).transpose()?,
+        };
+        let format = trace::Format::from_file_name(file.as_ref());
+        let out =
+            BufWriter::new(File::create(file).with_context(|| format!("failed to create {file}"))?);
+        let records = trace::record(out, format, &crossings, &path, &roles, &jumps, &options)?;
+        println!("wrote {records} trace records to {file}");
+    }
+
+    // networks made for other --start/--goal matchers may lack AAA or ZZZ,
+    // which shouldn't keep part 2 from running
+    match part1(&path, &crossings) {
+        Ok(steps) => println!("part 1: {steps}"),
+        Err(e) => println!("part 1: skipped, {e}"),
+    }
+
+    let cycles: Vec<_> = roles
+        .starts
+        .iter()
+        .map(|&start| crossings.paths_to_goals(start, &path, &roles))
+        .try_collect()?;
+    for (&start, cycle) in roles.starts.iter().zip(&cycles) {
+        println!(
+            "{}: cycle of {} steps after {}, goals after {:?}",
+            crossings.display(start),
+            cycle.cycle_len,
+            cycle.prefix_len,
+            cycle.goals
+        );
+    }
+
+    if let Some(idx) = args.iter().position(|arg| arg == "--dot") {
+        let dot_path = args
+            .get(idx + 1)
+            .context("--dot needs a file to write to")?;
+        let reachable_only = args.iter().any(|arg| arg == "--dot-reachable");
+        let dot = dot::to_dot(&crossings, &path, &roles, &cycles, reachable_only)?;
+        std::fs::write(dot_path, dot).with_context(|| format!("failed to write {dot_path}"))?;
+    }
+
+    // fail fast instead of simulating forever, but only after the diagnostics
+    // above that help to find out why
+    let reaches = reach::analyze(&jumps, &path, &roles, &cycles);
+    let common = reach::check_common_arrival(&crossings, &reaches, &cycles)?;
+
+    if args.iter().any(|arg| arg == "--lockstep") {
+        println!("part 2: {}", lockstep(&path, &crossings, &roles)?);
+        return Ok(());
+    }
+
+    let steps = match schedule::lcm_shortcut(&cycles) {
+        Some(steps) => {
+            println!("every cycle ends on its only goal, the lcm of the cycle lengths agrees");
+            debug_assert_eq!(steps, common);
+            steps
+        }
+        None => common,
+    };
+    println!("part 2: {steps}");
+
+    let verified = roles
+        .starts
+        .iter()
+        .all(|&start| roles.is_goal(jumps.position_after(start, steps)));
+    ensure!(
+        verified,
+        "not every walker is on a goal after {steps} steps"
+    );
+
+    if let Some(idx) = args.iter().position(|arg| arg == "--at") {
+        let at: u64 = args
+            .get(idx + 1)
+            .context("--at needs a step count")?
+            .parse()?;
+        for &start in &roles.starts {
+            let next = jumps
+                .next_goal(start, at)
+                .map_or("never".to_string(), |goal| goal.to_string());
+            println!(
+                "{} is at {} after {at} steps, next on a goal after {next}",
+                crossings.display(start),
+                crossings.display(jumps.position_after(start, at)),
+            );
+        }
+    }
 
     Ok(())
 }
 
-mod test {}
+#[cfg(test)]
+mod test {
+    use itertools::Itertools;
+
+    use super::{
+        finish, lockstep, parse_collecting, parse_with_roles, part1, schedule, Crossing, Crossings,
+        Direction, GoalCycle, InputErrors, Labels, Matcher, Path, Roles, INPUT,
+    };
+
+    /// The path and the crossings, or every problem found with the input,
+    /// for tests that resolve their [Roles] themselves
+    pub(super) fn parse(input: &str) -> Result<(Path, Crossings), InputErrors> {
+        let mut errors = vec![];
+        let parsed = parse_collecting(input, &mut errors);
+        finish(parsed, errors)
+    }
+
+    /// the starts and goals of part 2: `**A` and `**Z`
+    pub(super) fn ghost_roles(crossings: &Crossings) -> Roles {
+        crossings
+            .roles(&"suffix:A".parse().unwrap(), &"suffix:Z".parse().unwrap())
+            .unwrap()
+    }
+
+    fn cycles(input: &str) -> Vec<GoalCycle> {
+        let (path, crossings) = parse(input).unwrap();
+        let roles = ghost_roles(&crossings);
+        roles
+            .starts
+            .iter()
+            .map(|&start| crossings.paths_to_goals(start, &path, &roles).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn example_cycles() {
+        assert_eq!(
+            cycles(include_str!("example2.txt")),
+            vec![
+                GoalCycle {
+                    prefix_len: 1,
+                    cycle_len: 2,
+                    goals: vec![2]
+                },
+                GoalCycle {
+                    prefix_len: 1,
+                    cycle_len: 6,
+                    goals: vec![3, 6]
+                },
+            ]
+        );
+        // AAA and BBB alternate twice through the whole path before reaching ZZZ
+        assert_eq!(
+            cycles(include_str!("example_loop.txt")),
+            vec![GoalCycle {
+                prefix_len: 6,
+                cycle_len: 3,
+                goals: vec![6, 7, 8]
+            }]
+        );
+        // the same network as example2, with digits
+        assert_eq!(
+            cycles(include_str!("example_digits.txt")),
+            cycles(include_str!("example2.txt"))
+        );
+    }
+
+    #[test]
+    fn labels_round_trip() {
+        let (_, crossings) = parse(INPUT).unwrap();
+        let printed = crossings
+            .sorted
+            .iter()
+            .map(|c| {
+                let [src, left, right] = [c.src, c.left, c.right].map(|loc| crossings.label(loc));
+                format!("{src} = ({left}, {right})")
+            })
+            .sorted()
+            .collect_vec();
+        assert_eq!(printed, INPUT.lines().skip(2).sorted().collect_vec());
+    }
+
+    #[test]
+    fn lookups_agree() {
+        let (_, crossings) = parse(INPUT).unwrap();
+        for c in &crossings.sorted {
+            for dir in [Direction::Left, Direction::Right] {
+                assert_eq!(
+                    crossings.go(c.src, dir).unwrap(),
+                    crossings.go_sorted(c.src, dir).unwrap()
+                );
+            }
+        }
+        let aaa = crossings.labels.get("AAA").unwrap();
+        let next = crossings.go(aaa, Direction::Right).unwrap();
+        assert_eq!(crossings.display(next).to_string(), "VLV");
+
+        // parsing rejects BBB, as it's only a destination, so build it by hand
+        let mut labels = Labels::default();
+        let [aaa, missing] = ["AAA", "BBB"].map(|label| labels.intern(label).unwrap());
+        let crossings = Crossings::new(
+            vec![Crossing {
+                src: aaa,
+                left: missing,
+                right: missing,
+            }],
+            labels,
+        );
+        assert!(crossings.go(aaa, Direction::Left).is_ok());
+        assert!(crossings.go(missing, Direction::Right).is_err());
+        assert!(crossings.go_sorted(missing, Direction::Right).is_err());
+    }
+
+    #[test]
+    fn configurable_roles() {
+        let (_, crossings) = parse(include_str!("example_digits.txt")).unwrap();
+        let starts = |start: &str, goal: &str| {
+            let roles = crossings.roles(&start.parse().unwrap(), &goal.parse().unwrap())?;
+            let goals = roles.goals.iter().filter(|&&goal| goal).count();
+            let starts = roles
+                .starts
+                .iter()
+                .map(|&loc| crossings.label(loc))
+                .collect::<Vec<_>>();
+            anyhow::Ok((starts, goals))
+        };
+        assert_eq!(
+            starts("suffix:A", "suffix:Z").unwrap(),
+            (vec!["11A", "22A"], 2)
+        );
+        assert_eq!(
+            starts("exact:22A", "oneof:11Z,XXX").unwrap(),
+            (vec!["22A"], 2)
+        );
+        assert_eq!(
+            starts("oneof:11A,11B,QQQ", "exact:22Z").unwrap(),
+            (vec!["11A", "11B"], 1)
+        );
+        assert_eq!(
+            starts("suffix:1A", "exact:ZZZ").unwrap_err().to_string(),
+            "no location matches the goal Exact(\"ZZZ\")"
+        );
+        assert!(starts("suffix:Q", "suffix:Q").is_err());
+    }
+
+    #[test]
+    fn validation() {
+        let input = "LX\n\nAAA = (BBB, CCC)\nBBB = (AAA, DDD)\nAAA = (BBB, BBB)\nCCC = BBB, AAA\na b = (AAA, AAA)";
+        let errors = parse(input).err().unwrap();
+        assert_eq!(
+            errors
+                .0
+                .iter()
+                .map(|e| (e.line, e.message.as_str()))
+                .collect_vec(),
+            vec![
+                (Some(1), "invalid direction X"),
+                (Some(3), "AAA leads to CCC, which is never defined"),
+                (Some(4), "BBB leads to DDD, which is never defined"),
+                (Some(5), "AAA is already defined on line 3"),
+                (Some(6), "expected '(LEFT, RIGHT)', got 'BBB, AAA'"),
+                (Some(7), "invalid location \"a b\""),
+            ]
+        );
+        assert!(errors
+            .to_string()
+            .starts_with("6 problem(s) with the input:\n  line 1: invalid direction X\n"));
+
+        let errors = parse("\nAAA = (AAA, AAA)").err().unwrap();
+        assert_eq!(
+            errors.0.iter().map(|e| e.line).collect_vec(),
+            vec![Some(1), Some(2)],
+            "{errors}"
+        );
+        assert_eq!(parse("").err().unwrap().0[0].message, "missing the path");
+
+        // missing starts and goals are reported along with the broken lines
+        let errors = parse_with_roles(
+            "L\n\nBBB = (CCC, CCZ)\nCCZ = (CCZ, CCZ)",
+            &"suffix:A".parse().unwrap(),
+            &"suffix:Q".parse().unwrap(),
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            errors.to_string(),
+            "3 problem(s) with the input:\n  \
+             no location matches the start Suffix(\"A\")\n  \
+             no location matches the goal Suffix(\"Q\")\n  \
+             line 3: BBB leads to CCC, which is never defined\n"
+        );
+        assert!(parse_with_roles(
+            include_str!("example2.txt"),
+            &"suffix:A".parse().unwrap(),
+            &"suffix:Z".parse().unwrap()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn example_part1() {
+        for (input, steps) in [
+            (include_str!("example.txt"), 2),
+            (include_str!("example_loop.txt"), 6),
+        ] {
+            let (path, crossings) = parse(input).unwrap();
+            assert_eq!(part1(&path, &crossings).unwrap(), steps);
+        }
+    }
+
+    #[test]
+    fn part1_detects_loops() {
+        // AAZ is a goal for the walkers of part 2, but not for part 1
+        let (path, crossings) = parse(include_str!("example2.txt")).unwrap();
+        assert!(part1(&path, &crossings).is_err());
+
+        let (path, crossings) =
+            parse("RL\n\nAAA = (BBB, BBB)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)").unwrap();
+        assert!(part1(&path, &crossings).is_err());
+        let (path, crossings) =
+            parse("LR\n\nAAA = (BBB, BBB)\nBBB = (AAA, ZZZ)\nZZZ = (ZZZ, ZZZ)").unwrap();
+        assert_eq!(part1(&path, &crossings).unwrap(), 2);
+    }
+
+    #[test]
+    fn example_part2() {
+        for input in [
+            include_str!("example2.txt"),
+            include_str!("example_digits.txt"),
+        ] {
+            let cycles = cycles(input);
+            assert_eq!(schedule::lcm_shortcut(&cycles), None);
+            assert_eq!(schedule::first_common_goal(&cycles).unwrap(), Some(6));
+
+            let (path, crossings) = parse(input).unwrap();
+            assert_eq!(
+                lockstep(&path, &crossings, &ghost_roles(&crossings)).unwrap(),
+                6
+            );
+        }
+    }
+
+    #[test]
+    fn empty_path() {
+        let (_, crossings) = parse(include_str!("example.txt")).unwrap();
+        let roles = crossings
+            .roles(&Matcher::Exact("AAA".into()), &Matcher::Exact("ZZZ".into()))
+            .unwrap();
+        assert!(crossings
+            .paths_to_goals(roles.starts[0], &super::Path(vec![]), &roles)
+            .is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use std::hint::black_box;
+
+use anyhow::{ensure, Result};
+
+use super::{parse_with_roles, Crossings, Direction, Location, Path, INPUT};
+
+type Lookup = fn(&Crossings, Location, Direction) -> Result<Location>;
+
+/// The ghosts of the puzzle input, for `benches/lookups.rs` to time the dense
+/// table of [Crossings::go] against the binary search of [Crossings::go_sorted]
+pub struct Walkers {
+    path: Path,
+    crossings: Crossings,
+    starts: Vec<Location>,
+}
+
+impl Walkers {
+    /// every location ending in `A` is a start
+    pub fn from_input() -> Result<Self> {
+        let (path, crossings, roles) =
+            parse_with_roles(INPUT, &"suffix:A".parse()?, &"suffix:Z".parse()?)?;
+        let starts = roles.starts;
+        let walkers = Self {
+            path,
+            crossings,
+            starts,
+        };
+        ensure!(
+            walkers.dense(1000)? == walkers.sorted(1000)?,
+            "the lookups disagree"
+        );
+        Ok(walkers)
+    }
+
+    pub fn len(&self) -> usize {
+        self.starts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.starts.is_empty()
+    }
+
+    /// walks every start for `steps` steps with [Crossings::go], returns the
+    /// indices of where the walkers ended up
+    pub fn dense(&self, steps: usize) -> Result<Vec<usize>> {
+        self.walk(steps, Crossings::go)
+    }
+
+    /// like [Walkers::dense], but with [Crossings::go_sorted]
+    pub fn sorted(&self, steps: usize) -> Result<Vec<usize>> {
+        self.walk(steps, Crossings::go_sorted)
+    }
+
+    fn walk(&self, steps: usize, go: Lookup) -> Result<Vec<usize>> {
+        let mut locs = self.starts.clone();
+        for dir in self.path.into_iter().take(steps) {
+            for loc in &mut locs {
+                *loc = go(black_box(&self.crossings), *loc, dir)?;
+            }
+        }
+        Ok(locs.iter().map(Location::index).collect())
+    }
+}
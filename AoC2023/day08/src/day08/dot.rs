@@ -0,0 +1,195 @@
+use std::{
+    collections::{BTreeMap, HashSet, VecDeque},
+    fmt::Write,
+};
+
+use anyhow::Result;
+use itertools::Itertools;
+
+use super::{Crossings, Direction, GoalCycle, Location, Path, Roles};
+
+/// one color per walker, reused if there are more walkers
+const PALETTE: [&str; 6] = [
+    "#1c7ed6", "#e03131", "#2f9e44", "#f08c00", "#7048e8", "#0c8599",
+];
+const START_FILL: &str = "#b2f2bb";
+const GOAL_FILL: &str = "#ffc9c9";
+
+/// The crossings the walker from `start` takes over and over again once it
+/// is in its `cycle`
+fn cycle_edges(
+    crossings: &Crossings,
+    path: &Path,
+    start: Location,
+    cycle: &GoalCycle,
+) -> Result<HashSet<(Location, Direction)>> {
+    let mut edges = HashSet::new();
+    let mut loc = start;
+    for (step, dir) in path
+        .into_iter()
+        .take(cycle.prefix_len + cycle.cycle_len)
+        .enumerate()
+    {
+        if step >= cycle.prefix_len {
+            edges.insert((loc, dir));
+        }
+        loc = crossings.go(loc, dir)?;
+    }
+    Ok(edges)
+}
+
+/// every location reachable from any start, starts included
+fn reachable(crossings: &Crossings, roles: &Roles) -> HashSet<Location> {
+    let mut seen: HashSet<_> = roles.starts.iter().copied().collect();
+    let mut queue: VecDeque<_> = roles.starts.iter().copied().collect();
+    while let Some(loc) = queue.pop_front() {
+        for dir in [Direction::Left, Direction::Right] {
+            if let Ok(next) = crossings.go(loc, dir) {
+                if seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+    seen
+}
+
+/// Graphviz DOT source of the network
+/// Edges are labelled with the direction(s) leading along them, starts and goals
+/// are filled and the cycle of the walker from `roles.starts[i]` (as described by
+/// `cycles[i]`) is drawn in the i-th color of [PALETTE]
+/// With `reachable_only` locations no start can reach are left out
+pub(super) fn to_dot(
+    crossings: &Crossings,
+    path: &Path,
+    roles: &Roles,
+    cycles: &[GoalCycle],
+    reachable_only: bool,
+) -> Result<String> {
+    let mut colors = BTreeMap::<(Location, Direction), Vec<&str>>::new();
+    for ((&start, cycle), color) in roles.starts.iter().zip(cycles).zip(PALETTE.iter().cycle()) {
+        for edge in cycle_edges(crossings, path, start, cycle)? {
+            colors.entry(edge).or_default().push(color);
+        }
+    }
+    let shown = reachable_only.then(|| reachable(crossings, roles));
+    let is_shown = |loc: &Location| shown.as_ref().is_none_or(|shown| shown.contains(loc));
+
+    let mut dot = String::new();
+    writeln!(dot, "digraph network {{").unwrap();
+    writeln!(dot, "  node [shape=circle, fontname=monospace];").unwrap();
+    for (loc, label) in crossings.labels.iter() {
+        if !is_shown(&loc) {
+            continue;
+        }
+        let style = match (roles.starts.contains(&loc), roles.is_goal(loc)) {
+            (true, true) => {
+                format!(" [shape=doublecircle, style=filled, fillcolor=\"{START_FILL}\"]")
+            }
+            (true, false) => format!(" [style=filled, fillcolor=\"{START_FILL}\"]"),
+            (false, true) => {
+                format!(" [shape=doublecircle, style=filled, fillcolor=\"{GOAL_FILL}\"]")
+            }
+            (false, false) => String::new(),
+        };
+        writeln!(dot, "  \"{label}\"{style};").unwrap();
+    }
+
+    for c in crossings.sorted.iter().filter(|c| is_shown(&c.src)) {
+        // a crossing leading to the same location both ways gets a single edge
+        let edges = if c.left == c.right {
+            vec![(
+                c.left,
+                "L/R",
+                [Direction::Left, Direction::Right].as_slice(),
+            )]
+        } else {
+            vec![
+                (c.left, "L", [Direction::Left].as_slice()),
+                (c.right, "R", [Direction::Right].as_slice()),
+            ]
+        };
+        for (dest, text, dirs) in edges {
+            let edge_colors = dirs
+                .iter()
+                .flat_map(|&dir| colors.get(&(c.src, dir)).into_iter().flatten())
+                .copied()
+                .unique()
+                .collect::<Vec<_>>();
+            let style = if edge_colors.is_empty() {
+                String::new()
+            } else {
+                // parallel lines for cycles shared by several walkers
+                format!(", color=\"{}\", penwidth=2", edge_colors.join(":"))
+            };
+            writeln!(
+                dot,
+                "  \"{}\" -> \"{}\" [label=\"{text}\"{style}];",
+                crossings.display(c.src),
+                crossings.display(dest)
+            )
+            .unwrap();
+        }
+    }
+    writeln!(dot, "}}").unwrap();
+    Ok(dot)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::day08::{
+        test::{ghost_roles, parse},
+        Matcher,
+    };
+
+    use super::to_dot;
+
+    #[test]
+    fn example_network() {
+        let (path, crossings) = parse(include_str!("../example2.txt")).unwrap();
+        let roles = ghost_roles(&crossings);
+        let cycles = roles
+            .starts
+            .iter()
+            .map(|&start| crossings.paths_to_goals(start, &path, &roles).unwrap())
+            .collect::<Vec<_>>();
+
+        let dot = to_dot(&crossings, &path, &roles, &cycles, false).unwrap();
+        assert!(dot.starts_with("digraph network {\n"));
+        assert!(dot.contains("  \"AAA\" [style=filled, fillcolor=\"#b2f2bb\"];\n"));
+        assert!(
+            dot.contains("  \"AAZ\" [shape=doublecircle, style=filled, fillcolor=\"#ffc9c9\"];\n")
+        );
+        // the first walker cycles AAB -R-> AAZ -L-> AAB
+        assert!(
+            dot.contains("  \"AAB\" -> \"AAZ\" [label=\"R\", color=\"#1c7ed6\", penwidth=2];\n")
+        );
+        assert!(
+            dot.contains("  \"AAZ\" -> \"AAB\" [label=\"L\", color=\"#1c7ed6\", penwidth=2];\n")
+        );
+        // but never goes left from AAB
+        assert!(dot.contains("  \"AAB\" -> \"XXX\" [label=\"L\"];\n"));
+        // the second walker passes BBB and BBC both ways
+        assert!(
+            dot.contains("  \"BBC\" -> \"BBZ\" [label=\"L/R\", color=\"#e03131\", penwidth=2];\n")
+        );
+        assert!(dot.contains("  \"XXX\" -> \"XXX\" [label=\"L/R\"];\n"));
+        assert!(dot.contains("  \"XXX\";\n"));
+        assert_eq!(dot.matches(" -> ").count(), 12);
+
+        // nothing leads from CCC back to AAA
+        let (path, crossings) =
+            parse("L\n\nAAA = (BBZ, CCC)\nCCC = (CCC, CCC)\nBBZ = (BBZ, BBZ)").unwrap();
+        let roles = crossings
+            .roles(&Matcher::Exact("CCC".into()), &Matcher::Suffix("Z".into()))
+            .unwrap();
+        let cycles = [crossings
+            .paths_to_goals(roles.starts[0], &path, &roles)
+            .unwrap()];
+        let dot = to_dot(&crossings, &path, &roles, &cycles, true).unwrap();
+        assert!(!dot.contains("AAA") && !dot.contains("BBZ"));
+        assert!(
+            dot.contains("  \"CCC\" -> \"CCC\" [label=\"L/R\", color=\"#1c7ed6\", penwidth=2];\n")
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use anyhow::{anyhow, Result};
+
+use super::{Crossings, Location, Path, Roles};
+
+/// enough doublings to cover every amount of passes that fits into a u64
+const LEVELS: usize = u64::BITS as usize;
+
+/// Where every location ends up after whole passes of the [Path], and when it
+/// is on a goal along the way
+/// Binary lifting over the table, together with the positions within a single
+/// pass, answers queries about the walk starting at any location (at the
+/// beginning of the path) in `O(log n)` instead of simulating all `n` steps
+pub(super) struct JumpTable {
+    path_len: u64,
+    /// `within[r][loc.index()]` is where `loc` is after `r < path length` steps
+    within: Vec<Vec<Location>>,
+    /// `passes[k][loc.index()]` is where `loc` ends up after `2^k` passes
+    passes: Vec<Vec<Location>>,
+    /// `any_goal[k][loc.index()]` tells if a goal is hit during the `2^k` passes
+    /// starting at `loc`, i.e. after any of the step counts `0..2^k * path length`
+    any_goal: Vec<Vec<bool>>,
+    /// the step counts `0..path length` into a single pass starting at `loc`
+    /// after which the walker is on a goal, ascending
+    pass_goals: Vec<Vec<u64>>,
+}
+
+impl JumpTable {
+    /// An error if the path is empty or some location can't complete a pass
+    /// Takes `O((path length + LEVELS) * locations)` time and memory
+    pub(super) fn new(crossings: &Crossings, path: &Path, roles: &Roles) -> Result<Self> {
+        if path.0.is_empty() {
+            return Err(anyhow!("the path is empty, there are no passes"));
+        }
+
+        let count = crossings.labels.len();
+        let mut within = vec![Vec::with_capacity(count); path.0.len()];
+        let mut first = Vec::with_capacity(count);
+        let mut pass_goals = Vec::with_capacity(count);
+        for (start, _) in crossings.labels.iter() {
+            let mut loc = start;
+            let mut goals = vec![];
+            for (step, &dir) in path.0.iter().enumerate() {
+                within[step].push(loc);
+                if roles.is_goal(loc) {
+                    goals.push(step as u64);
+                }
+                loc = crossings.go(loc, dir)?;
+            }
+            first.push(loc);
+            pass_goals.push(goals);
+        }
+
+        let mut any_goal: Vec<Vec<bool>> =
+            vec![pass_goals.iter().map(|goals| !goals.is_empty()).collect()];
+        let mut passes = vec![first];
+        for level in 1..LEVELS {
+            let (prev, prev_goal) = (&passes[level - 1], &any_goal[level - 1]);
+            let next = prev.iter().map(|mid| prev[mid.index()]).collect();
+            let next_goal = (0..count)
+                .map(|idx| prev_goal[idx] || prev_goal[prev[idx].index()])
+                .collect();
+            passes.push(next);
+            any_goal.push(next_goal);
+        }
+
+        Ok(Self {
+            path_len: path.0.len() as u64,
+            within,
+            passes,
+            any_goal,
+            pass_goals,
+        })
+    }
+
+    fn after_passes(&self, from: Location, passes: u64) -> Location {
+        (0..LEVELS)
+            .filter(|&level| passes >> level & 1 == 1)
+            .fold(from, |loc, level| self.passes[level][loc.index()])
+    }
+
+    /// where the walker starting at `from` is after `steps` steps, in `O(log steps)`
+    pub(super) fn position_after(&self, from: Location, steps: u64) -> Location {
+        let (passes, rest) = (steps / self.path_len, steps % self.path_len);
+        self.within[rest as usize][self.after_passes(from, passes).index()]
+    }
+
+    /// The first step count `>= steps` after which the walker starting at `from`
+    /// is on a goal, [None] if that never happens (within [u64::MAX] steps)
+    /// Takes `O(log steps + log path length)`
+    pub(super) fn next_goal(&self, from: Location, steps: u64) -> Option<u64> {
+        let (mut passes, rest) = (steps / self.path_len, steps % self.path_len);
+        let mut loc = self.after_passes(from, passes);
+        let goals = &self.pass_goals[loc.index()];
+        if let Some(goal) = goals.get(goals.partition_point(|&goal| goal < rest)) {
+            return (passes * self.path_len).checked_add(*goal);
+        }
+
+        // skip as many passes without goals as possible, largest jumps first
+        loc = self.passes[0][loc.index()];
+        passes = passes.checked_add(1)?;
+        for level in (0..LEVELS).rev() {
+            if !self.any_goal[level][loc.index()] {
+                passes = passes.checked_add(1 << level)?;
+                loc = self.passes[level][loc.index()];
+            }
+        }
+        let goal = self.pass_goals[loc.index()].first()?;
+        passes.checked_mul(self.path_len)?.checked_add(*goal)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::day08::{
+        test::{ghost_roles, parse},
+        Crossings, Path, Roles, INPUT,
+    };
+
+    use super::JumpTable;
+
+    /// `(location, is goal)` after every step count up to `steps`
+    fn simulate(
+        crossings: &Crossings,
+        path: &Path,
+        roles: &Roles,
+        from: &str,
+        steps: usize,
+    ) -> Vec<(String, bool)> {
+        let mut loc = crossings.labels.get(from).unwrap();
+        let mut dirs = path.into_iter();
+        (0..=steps)
+            .map(|_| {
+                let here = (crossings.label(loc).to_string(), roles.is_goal(loc));
+                loc = crossings.go(loc, dirs.next().unwrap()).unwrap();
+                here
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_simulation() {
+        for (input, starts) in [
+            // XXX only ever leads back to itself and never reaches a goal
+            (
+                include_str!("../example2.txt"),
+                ["AAA", "BBA", "XXX"].as_slice(),
+            ),
+            (INPUT, ["AAA", "JGA", "PXZ", "NQT"].as_slice()),
+        ] {
+            let (path, crossings) = parse(input).unwrap();
+            let roles = ghost_roles(&crossings);
+            let jumps = JumpTable::new(&crossings, &path, &roles).unwrap();
+            for start in starts {
+                let from = crossings.labels.get(start).unwrap();
+                // every checked step count is followed by at least a whole cycle,
+                // so a goal not found in the walk is never reached
+                let walk = simulate(&crossings, &path, &roles, start, 3 * 20_803);
+                for steps in (0..=2 * 20_803).step_by(97).chain([1, 283, 284, 13771]) {
+                    let loc = jumps.position_after(from, steps as u64);
+                    assert_eq!(crossings.label(loc), walk[steps].0, "{start} after {steps}");
+
+                    let next = walk[steps..].iter().position(|(_, goal)| *goal);
+                    let expected = next.map(|offset| (steps + offset) as u64);
+                    assert_eq!(
+                        jumps.next_goal(from, steps as u64),
+                        expected,
+                        "{start} from {steps}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn verifies_part2() {
+        let (path, crossings) = parse(INPUT).unwrap();
+        let roles = ghost_roles(&crossings);
+        let jumps = JumpTable::new(&crossings, &path, &roles).unwrap();
+        let answer = 13_129_439_557_681;
+        assert!(roles
+            .starts
+            .iter()
+            .all(|&start| roles.is_goal(jumps.position_after(start, answer))));
+        assert!(!roles
+            .starts
+            .iter()
+            .all(|&start| roles.is_goal(jumps.position_after(start, answer - 13771))));
+        for &start in &roles.starts {
+            assert_eq!(jumps.next_goal(start, answer), Some(answer));
+        }
+    }
+
+    #[test]
+    fn unreachable_goals() {
+        let (path, crossings) =
+            parse("LR\n\nAAA = (BBB, AAA)\nBBB = (AAA, BBB)\nCCZ = (CCZ, CCZ)").unwrap();
+        let roles = ghost_roles(&crossings);
+        let jumps = JumpTable::new(&crossings, &path, &roles).unwrap();
+        let aaa = crossings.labels.get("AAA").unwrap();
+        let ccz = crossings.labels.get("CCZ").unwrap();
+        assert_eq!(jumps.next_goal(aaa, 0), None);
+        assert_eq!(jumps.next_goal(ccz, 12345), Some(12345));
+        assert_eq!(jumps.next_goal(ccz, u64::MAX), Some(u64::MAX));
+        assert_eq!(jumps.position_after(aaa, u64::MAX), aaa);
+
+        assert!(JumpTable::new(&crossings, &Path(vec![]), &roles).is_err());
+    }
+}
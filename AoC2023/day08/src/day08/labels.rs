@@ -0,0 +1,171 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Dense id of an interned node label, see [Labels]
+/// Ids are handed out in order of first appearance, starting at zero
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(super) struct Location(u32);
+
+impl Location {
+    /// placeholder for missing entries, never handed out by [Labels::intern]
+    pub(super) const NONE: Location = Location(u32::MAX);
+
+    /// dense index in `0..labels.len()`
+    pub(super) fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Maps node labels of any length to [Location]s and back
+#[derive(Debug, Default)]
+pub(super) struct Labels {
+    names: Vec<String>,
+    ids: HashMap<String, Location>,
+}
+
+impl Labels {
+    /// The id of `label`, a new one if it wasn't seen before
+    /// Labels may consist of letters, digits and underscores
+    pub(super) fn intern(&mut self, label: &str) -> Result<Location> {
+        if let Some(&loc) = self.ids.get(label) {
+            return Ok(loc);
+        }
+        let valid = !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid {
+            return Err(anyhow!("invalid location {label:?}"));
+        }
+
+        let loc = u32::try_from(self.names.len())
+            .ok()
+            .filter(|&id| id != Location::NONE.0)
+            .map(Location)
+            .context("too many distinct locations")?;
+        self.names.push(label.to_string());
+        self.ids.insert(label.to_string(), loc);
+        Ok(loc)
+    }
+
+    pub(super) fn get(&self, label: &str) -> Option<Location> {
+        self.ids.get(label).copied()
+    }
+
+    /// the label `loc` was interned from
+    pub(super) fn name(&self, loc: Location) -> &str {
+        &self.names[loc.index()]
+    }
+
+    /// `loc` printed as the label it was interned from
+    pub(super) fn display(&self, loc: Location) -> LabelRef<'_> {
+        LabelRef { labels: self, loc }
+    }
+
+    /// all labels with their ids, in the order of the ids
+    pub(super) fn iter(&self) -> impl Iterator<Item = (Location, &str)> {
+        (0..)
+            .map(Location)
+            .zip(self.names.iter().map(String::as_str))
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.names.len()
+    }
+}
+
+/// A [Location] together with the [Labels] it was interned in, see [Labels::display]
+#[derive(Clone, Copy, Debug)]
+pub(super) struct LabelRef<'a> {
+    labels: &'a Labels,
+    loc: Location,
+}
+
+impl Display for LabelRef<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.labels.name(self.loc))
+    }
+}
+
+/// Decides which labels count as a start or a goal
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) enum Matcher {
+    Suffix(String),
+    Exact(String),
+    OneOf(BTreeSet<String>),
+}
+
+impl Matcher {
+    pub(super) fn matches(&self, label: &str) -> bool {
+        match self {
+            Matcher::Suffix(suffix) => label.ends_with(suffix.as_str()),
+            Matcher::Exact(name) => label == name,
+            Matcher::OneOf(names) => names.contains(label),
+        }
+    }
+}
+
+/// `suffix:A`, `exact:AAA` or `oneof:AAA,11A`
+impl FromStr for Matcher {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, arg) = s
+            .split_once(':')
+            .with_context(|| format!("expected `kind:labels`, got {s:?}"))?;
+        match kind {
+            "suffix" => Ok(Matcher::Suffix(arg.to_string())),
+            "exact" => Ok(Matcher::Exact(arg.to_string())),
+            "oneof" => Ok(Matcher::OneOf(arg.split(',').map(String::from).collect())),
+            other => Err(anyhow!(
+                "unknown matcher {other:?}, expected suffix, exact or oneof"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Labels, Location, Matcher};
+
+    #[test]
+    fn interning() {
+        let mut labels = Labels::default();
+        let first = labels.intern("11A").unwrap();
+        let second = labels.intern("LONG_label").unwrap();
+        assert_eq!(labels.intern("11A").unwrap(), first);
+        assert_eq!((first, second), (Location(0), Location(1)));
+        assert_eq!(labels.name(second), "LONG_label");
+        assert_eq!(labels.get("11A"), Some(first));
+        assert_eq!(labels.get("22A"), None);
+        assert_eq!(labels.len(), 2);
+
+        // printing a location gives back the label it was interned from
+        for label in ["11A", "LONG_label"] {
+            let printed = labels.display(labels.get(label).unwrap()).to_string();
+            assert_eq!(printed, label);
+            assert_eq!(labels.get(&printed), labels.get(label));
+        }
+
+        assert!(labels.intern("").is_err());
+        assert!(labels.intern("A A").is_err());
+        assert!(labels.intern("(AAA").is_err());
+    }
+
+    #[test]
+    fn matchers() {
+        let suffix: Matcher = "suffix:Z".parse().unwrap();
+        assert!(suffix.matches("11Z") && suffix.matches("Z") && !suffix.matches("ZA"));
+
+        let exact: Matcher = "exact:AAA".parse().unwrap();
+        assert!(exact.matches("AAA") && !exact.matches("BAAA"));
+
+        let one_of: Matcher = "oneof:AAA,11A".parse().unwrap();
+        assert!(one_of.matches("11A") && one_of.matches("AAA") && !one_of.matches("22A"));
+
+        assert!("AAA".parse::<Matcher>().is_err());
+        assert!("prefix:A".parse::<Matcher>().is_err());
+    }
+}
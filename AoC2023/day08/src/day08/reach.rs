@@ -0,0 +1,176 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Context, Result};
+use itertools::Itertools;
+
+use super::{jump::JumpTable, schedule, Crossings, GoalCycle, Location, Path, Roles};
+
+/// The goals a single walker can ever be on
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct Reach {
+    pub(super) start: Location,
+    /// every reachable goal, with the path positions the walker is on it at
+    pub(super) goals: BTreeMap<Location, BTreeSet<usize>>,
+}
+
+impl Reach {
+    fn positions(&self) -> BTreeSet<usize> {
+        self.goals.values().flatten().copied().collect()
+    }
+}
+
+/// Reads off which goals every start of `roles` is on at which path positions
+/// from its [GoalCycle] (`cycles[i]` belongs to `roles.starts[i]`), which already
+/// covers every `(location, path position)` state the walker ever gets into
+pub(super) fn analyze(
+    jumps: &JumpTable,
+    path: &Path,
+    roles: &Roles,
+    cycles: &[GoalCycle],
+) -> Vec<Reach> {
+    roles
+        .starts
+        .iter()
+        .zip(cycles)
+        .map(|(&start, cycle)| {
+            let mut goals = BTreeMap::<_, BTreeSet<_>>::new();
+            for &step in &cycle.goals {
+                let goal = jumps.position_after(start, step as u64);
+                goals.entry(goal).or_default().insert(step % path.0.len());
+            }
+            Reach { start, goals }
+        })
+        .collect()
+}
+
+/// The first step count after which every walker is on a goal, or an explanation
+/// why that never happens
+///
+/// [analyze] already tells if a walker never reaches any goal, or if there is
+/// no path position at which every walker can be on a goal, as a walker on a goal
+/// at position `i` has taken `i` steps modulo the path length
+/// Otherwise the `cycles` are solved with [schedule::first_common_goal]
+pub(super) fn check_common_arrival(
+    crossings: &Crossings,
+    reaches: &[Reach],
+    cycles: &[GoalCycle],
+) -> Result<u64> {
+    let stuck = reaches
+        .iter()
+        .filter(|reach| reach.goals.is_empty())
+        .map(|reach| crossings.label(reach.start))
+        .collect_vec();
+    if !stuck.is_empty() {
+        return Err(anyhow!(
+            "no goal can be reached from {}, so the walkers never all arrive at once",
+            stuck.join(", ")
+        ));
+    }
+
+    let common = reaches
+        .iter()
+        .map(Reach::positions)
+        .reduce(|common, positions| &common & &positions)
+        .unwrap_or_default();
+    if common.is_empty() && !reaches.is_empty() {
+        let positions = reaches
+            .iter()
+            .map(|reach| {
+                format!(
+                    "{} at {:?}",
+                    crossings.display(reach.start),
+                    reach.positions()
+                )
+            })
+            .join(", ");
+        return Err(anyhow!(
+            "the walkers are never on goals at the same path position: {positions}"
+        ));
+    }
+
+    schedule::first_common_goal(cycles)?.context(
+        "the walkers share path positions on goals, \
+         but are never all on goals after the same amount of steps",
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use anyhow::Result;
+
+    use crate::day08::{
+        jump::JumpTable,
+        test::{ghost_roles, parse},
+        Crossings,
+    };
+
+    use super::{analyze, check_common_arrival, Reach};
+
+    /// the reaches of the ghosts of `input`, with the verdict on their common arrival
+    fn verdict(input: &str) -> (Crossings, Vec<Reach>, Result<u64>) {
+        let (path, crossings) = parse(input).unwrap();
+        let roles = ghost_roles(&crossings);
+        let cycles = roles
+            .starts
+            .iter()
+            .map(|&start| crossings.paths_to_goals(start, &path, &roles).unwrap())
+            .collect::<Vec<_>>();
+        let jumps = JumpTable::new(&crossings, &path, &roles).unwrap();
+        let reaches = analyze(&jumps, &path, &roles, &cycles);
+        let common = check_common_arrival(&crossings, &reaches, &cycles);
+        (crossings, reaches, common)
+    }
+
+    #[test]
+    fn example_reach() {
+        let (crossings, reaches, common) = verdict(include_str!("../example2.txt"));
+        let goals = reaches
+            .iter()
+            .map(|reach| {
+                reach
+                    .goals
+                    .iter()
+                    .map(|(&goal, positions)| (crossings.label(goal), positions.clone()))
+                    .collect::<BTreeMap<_, _>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            goals,
+            vec![
+                BTreeMap::from([("AAZ", BTreeSet::from([0]))]),
+                BTreeMap::from([("BBZ", BTreeSet::from([0, 1]))]),
+            ]
+        );
+        assert_eq!(common.unwrap(), 6);
+    }
+
+    #[test]
+    fn explains_impossible_arrivals() {
+        // AAA is only on AAZ after odd, BBA only on BBZ after even step counts
+        let input = "LR\n\nAAA = (AAZ, AAZ)\nAAZ = (AAZ, AAA)\n\
+                     BBA = (BBB, BBB)\nBBB = (BBZ, BBZ)\nBBZ = (BBB, BBB)";
+        assert_eq!(
+            verdict(input).2.unwrap_err().to_string(),
+            "the walkers are never on goals at the same path position: AAA at {1}, BBA at {0}"
+        );
+
+        assert_eq!(
+            verdict(&format!("{input}\nCCA = (CCA, CCA)"))
+                .2
+                .unwrap_err()
+                .to_string(),
+            "no goal can be reached from CCA, so the walkers never all arrive at once"
+        );
+
+        // the same with a path of length one, where every goal is at position 0
+        let input = "L\n\nAAA = (AAZ, AAZ)\nAAZ = (AAA, AAA)\n\
+                     BBA = (BBB, BBB)\nBBB = (BBZ, BBZ)\nBBZ = (BBB, BBB)";
+        assert_eq!(
+            verdict(input).2.unwrap_err().to_string(),
+            "the walkers share path positions on goals, \
+             but are never all on goals after the same amount of steps"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use anyhow::{anyhow, Result};
+use itertools::Itertools;
+
+use super::GoalCycle;
+
+/// all step counts `t` with `t ≡ residue (mod modulus)`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Congruence {
+    residue: u128,
+    modulus: u128,
+}
+
+impl Congruence {
+    /// Chinese remainder theorem for moduli that don't have to be coprime
+    /// Ok([None]) if no step count satisfies both, an error if the combined
+    /// modulus overflows
+    fn merge(self, other: Self) -> Result<Option<Self>> {
+        let (r1, m1) = (self.residue, self.modulus);
+        let (r2, m2) = (other.residue, other.modulus);
+        let g = gcd(m1, m2);
+        // t = r1 + m1 * k  =>  m1 * k ≡ r2 - r1 (mod m2)
+        let diff = (r2 % m2 + m2 - r1 % m2) % m2;
+        if diff % g != 0 {
+            return Ok(None);
+        }
+        let m2_reduced = m2 / g;
+        let inv =
+            mod_inverse((m1 / g) % m2_reduced, m2_reduced).expect("m1 / g and m2 / g are coprime");
+        let k = (diff / g) % m2_reduced * inv % m2_reduced;
+
+        let overflow = || anyhow!("the combined cycle of {m1} and {m2} steps is too long");
+        let modulus = (m1 / g).checked_mul(m2).ok_or_else(overflow)?;
+        let residue = m1
+            .checked_mul(k)
+            .and_then(|offset| offset.checked_add(r1 % m1))
+            .ok_or_else(overflow)?
+            % modulus;
+        Ok(Some(Self { residue, modulus }))
+    }
+
+    /// smallest step count `>= min` that satisfies the congruence
+    fn first_from(&self, min: u128) -> u128 {
+        let below = min % self.modulus;
+        let residue = self.residue % self.modulus;
+        if residue >= below {
+            min - below + residue
+        } else {
+            min - below + self.modulus + residue
+        }
+    }
+}
+
+fn gcd(a: u128, b: u128) -> u128 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// [None] if `a` isn't invertible modulo `m`
+fn mod_inverse(a: u128, m: u128) -> Option<u128> {
+    if m == 1 {
+        return Some(0);
+    }
+    // extended euclid, the coefficients stay below m so they fit into an i128
+    // as long as m does, which holds for cycles found by walking the map
+    let (mut old_r, mut r) = (a as i128, m as i128);
+    let (mut old_s, mut s) = (1i128, 0i128);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_s, s) = (s, old_s - q * s);
+    }
+    (old_r == 1).then(|| old_s.rem_euclid(m as i128) as u128)
+}
+
+/// The step counts after which a single walker is on a goal
+#[derive(Debug)]
+struct Schedule {
+    /// goals before the cycle, only hit once
+    once: Vec<u128>,
+    /// from here on the walker is in its cycle
+    cycle_start: u128,
+    /// one congruence per goal in the cycle, all sharing the cycle length as modulus
+    periodic: Vec<Congruence>,
+}
+
+impl Schedule {
+    fn new(cycle: &GoalCycle) -> Self {
+        let (prefix, cycle_len) = (cycle.prefix_len as u128, cycle.cycle_len as u128);
+        let (once, periodic) = cycle
+            .goals
+            .iter()
+            .map(|&goal| goal as u128)
+            .partition::<Vec<_>, _>(|&goal| goal < prefix);
+        Self {
+            once,
+            cycle_start: prefix,
+            periodic: periodic
+                .into_iter()
+                .map(|goal| Congruence {
+                    residue: goal % cycle_len,
+                    modulus: cycle_len,
+                })
+                .collect(),
+        }
+    }
+
+    fn hits(&self, step: u128) -> bool {
+        self.once.contains(&step)
+            || (step >= self.cycle_start
+                && self.periodic.iter().any(|c| step % c.modulus == c.residue))
+    }
+}
+
+/// The first step count (at least one step) after which every walker is on a goal
+/// at the same time, [None] if that never happens or there are no walkers
+///
+/// Hits before every walker entered its cycle are checked one by one, afterwards
+/// every combination of goals (one per walker) is solved as a system of congruences
+pub(super) fn first_common_goal(cycles: &[GoalCycle]) -> Result<Option<u64>> {
+    let schedules = cycles.iter().map(Schedule::new).collect_vec();
+    let Some(all_cycling) = schedules.iter().map(|s| s.cycle_start).max() else {
+        return Ok(None);
+    };
+
+    let early = schedules
+        .iter()
+        .flat_map(|s| s.once.iter().copied())
+        .filter(|&step| step >= 1 && step < all_cycling)
+        .sorted()
+        .find(|&step| schedules.iter().all(|s| s.hits(step)));
+    if let Some(step) = early {
+        return to_steps(step).map(Some);
+    }
+
+    let mut combined = vec![Congruence {
+        residue: 0,
+        modulus: 1,
+    }];
+    for schedule in &schedules {
+        let mut next = vec![];
+        for (acc, goal) in combined.iter().cartesian_product(&schedule.periodic) {
+            if let Some(merged) = acc.merge(*goal)? {
+                next.push(merged);
+            }
+        }
+        // different goals of a walker may fall together with the ones before
+        combined = next.into_iter().unique_by(|c| c.residue).collect();
+    }
+
+    combined
+        .iter()
+        .map(|c| c.first_from(all_cycling.max(1)))
+        .min()
+        .map(to_steps)
+        .transpose()
+}
+
+/// Only valid if every walker has exactly one goal, reached right at the end of
+/// its first cycle (and then every `cycle_len` steps), which makes the answer
+/// the least common multiple of the cycle lengths
+/// [None] if `cycles` don't meet these assumptions
+pub(super) fn lcm_shortcut(cycles: &[GoalCycle]) -> Option<u64> {
+    if cycles.is_empty() {
+        return None;
+    }
+    cycles.iter().try_fold(1u64, |acc, cycle| {
+        let cycle_len = cycle.cycle_len as u64;
+        let applies = cycle.goals == [cycle.cycle_len] && cycle.prefix_len <= cycle.cycle_len;
+        applies
+            .then(|| (acc / gcd(acc.into(), cycle_len.into()) as u64).checked_mul(cycle_len))
+            .flatten()
+    })
+}
+
+fn to_steps(step: u128) -> Result<u64> {
+    step.try_into()
+        .map_err(|_| anyhow!("{step} steps don't fit into a u64"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{first_common_goal, lcm_shortcut, Congruence};
+    use crate::day08::GoalCycle;
+
+    fn congruence(residue: u128, modulus: u128) -> Congruence {
+        Congruence { residue, modulus }
+    }
+
+    fn cycle(prefix_len: usize, cycle_len: usize, goals: &[usize]) -> GoalCycle {
+        GoalCycle {
+            prefix_len,
+            cycle_len,
+            goals: goals.to_vec(),
+        }
+    }
+
+    /// walks every step count up to `limit`
+    fn naive(cycles: &[GoalCycle], limit: usize) -> Option<u64> {
+        (1..limit)
+            .find(|&step| {
+                cycles.iter().all(|c| {
+                    c.goals.iter().any(|&goal| {
+                        goal == step
+                            || (goal >= c.prefix_len
+                                && step > goal
+                                && (step - goal) % c.cycle_len == 0)
+                    })
+                })
+            })
+            .map(|step| step as u64)
+    }
+
+    #[test]
+    fn merge_congruences() {
+        assert_eq!(
+            congruence(2, 4).merge(congruence(4, 6)).unwrap(),
+            Some(congruence(10, 12))
+        );
+        assert_eq!(congruence(1, 4).merge(congruence(2, 6)).unwrap(), None);
+        assert_eq!(
+            congruence(2, 3).merge(congruence(3, 5)).unwrap(),
+            Some(congruence(8, 15))
+        );
+        assert!(congruence(0, u128::MAX)
+            .merge(congruence(0, u128::MAX - 1))
+            .is_err());
+        assert_eq!(congruence(10, 12).first_from(11), 22);
+        assert_eq!(congruence(10, 12).first_from(10), 10);
+    }
+
+    #[test]
+    fn offsets_and_multiple_goals() {
+        // hits 1, then every even step from 4 on
+        let a = cycle(3, 4, &[1, 4, 6]);
+        // hits every 6 steps, starting with 5
+        let b = cycle(0, 6, &[5]);
+        // hits 2, then 7, 16, 25, ..
+        let c = cycle(5, 9, &[2, 7]);
+        // hits 1 and 2 before cycling on 13, 14, 26, 27, ..
+        let d = cycle(3, 13, &[1, 2, 13, 14]);
+
+        let cases = [
+            vec![a.clone(), b.clone()],
+            vec![a.clone(), c.clone()],
+            vec![b.clone(), c.clone()],
+            vec![a.clone(), d.clone()],
+            vec![c.clone(), d.clone()],
+            vec![a.clone(), c, d],
+            vec![b],
+        ];
+        for cycles in cases {
+            assert_eq!(
+                first_common_goal(&cycles).unwrap(),
+                naive(&cycles, 10_000),
+                "{cycles:?}"
+            );
+        }
+        assert_eq!(
+            first_common_goal(&[a.clone(), cycle(3, 13, &[1])]).unwrap(),
+            Some(1)
+        );
+        assert_eq!(first_common_goal(&[]).unwrap(), None);
+        assert_eq!(lcm_shortcut(&[a]), None);
+    }
+
+    #[test]
+    fn lcm_only_when_applicable() {
+        let cycles = [cycle(2, 4, &[4]), cycle(1, 6, &[6])];
+        assert_eq!(lcm_shortcut(&cycles), Some(12));
+        assert_eq!(first_common_goal(&cycles).unwrap(), Some(12));
+
+        // the goal isn't at the end of the cycle
+        let shifted = [cycle(2, 4, &[4]), cycle(1, 6, &[3])];
+        assert_eq!(lcm_shortcut(&shifted), None);
+        assert_eq!(first_common_goal(&shifted).unwrap(), naive(&shifted, 100));
+        // the goal is only hit before the cycle starts
+        assert_eq!(lcm_shortcut(&[cycle(8, 4, &[4])]), None);
+        assert_eq!(lcm_shortcut(&[]), None);
+    }
+}
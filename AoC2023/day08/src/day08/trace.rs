@@ -0,0 +1,200 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use serde_json::json;
+
+use super::{jump::JumpTable, Crossings, Matcher, Path, Roles};
+
+/// how each recorded step is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Format {
+    /// with a header line
+    Csv,
+    /// one JSON object per line
+    JsonLines,
+}
+
+impl Format {
+    /// JSON lines for `.jsonl` and `.json` files, CSV otherwise
+    pub(super) fn from_file_name(file: &std::path::Path) -> Self {
+        if file
+            .extension()
+            .is_some_and(|ext| ext == "jsonl" || ext == "json")
+        {
+            Format::JsonLines
+        } else {
+            Format::Csv
+        }
+    }
+}
+
+/// Which steps of which walkers are recorded
+#[derive(Debug)]
+pub(super) struct TraceOptions {
+    /// only every n-th step of the window, starting with its first one
+    pub(super) every: u64,
+    /// first step count that is recorded
+    pub(super) from: u64,
+    /// step count at which recording stops, exclusive
+    pub(super) until: u64,
+    /// only walkers whose start matches, all of them if [None]
+    pub(super) walkers: Option<Matcher>,
+}
+
+/// Walks every (selected) start of `roles` through the window of `options` and
+/// writes where each walker is after the sampled step counts, together with the
+/// position inside the path and whether it is on a goal
+/// The walkers are fast forwarded to the start of the window with `jumps`
+/// Returns the amount of records written
+pub(super) fn record(
+    mut out: impl Write,
+    format: Format,
+    crossings: &Crossings,
+    path: &Path,
+    roles: &Roles,
+    jumps: &JumpTable,
+    options: &TraceOptions,
+) -> Result<usize> {
+    if options.every == 0 {
+        return Err(anyhow!("can't record every 0th step"));
+    }
+    let mut walkers = roles
+        .starts
+        .iter()
+        .filter(|&&start| {
+            options
+                .walkers
+                .as_ref()
+                .is_none_or(|walkers| walkers.matches(crossings.label(start)))
+        })
+        .map(|&start| (start, jumps.position_after(start, options.from)))
+        .collect::<Vec<_>>();
+
+    if format == Format::Csv {
+        writeln!(out, "step,walker,location,path_position,goal")?;
+    }
+    let mut records = 0;
+    for step in options.from..options.until {
+        let path_position = (step % path.0.len() as u64) as usize;
+        if (step - options.from).is_multiple_of(options.every) {
+            for &(start, loc) in &walkers {
+                let (walker, location) = (crossings.label(start), crossings.label(loc));
+                let goal = roles.is_goal(loc);
+                match format {
+                    Format::Csv => {
+                        writeln!(out, "{step},{walker},{location},{path_position},{goal}")?
+                    }
+                    Format::JsonLines => writeln!(
+                        out,
+                        "{}",
+                        json!({
+                            "step": step,
+                            "walker": walker,
+                            "location": location,
+                            "path_position": path_position,
+                            "goal": goal,
+                        })
+                    )?,
+                }
+                records += 1;
+            }
+        }
+        for (_, loc) in &mut walkers {
+            *loc = crossings.go(*loc, path.0[path_position])?;
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use crate::day08::{
+        jump::JumpTable,
+        test::{ghost_roles, parse},
+        Matcher,
+    };
+
+    use super::{record, Format, TraceOptions};
+
+    fn trace(format: Format, options: TraceOptions) -> String {
+        let (path, crossings) = parse(include_str!("../example2.txt")).unwrap();
+        let roles = ghost_roles(&crossings);
+        let jumps = JumpTable::new(&crossings, &path, &roles).unwrap();
+        let mut out = vec![];
+        record(
+            &mut out, format, &crossings, &path, &roles, &jumps, &options,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_window() {
+        let csv = trace(
+            Format::Csv,
+            TraceOptions {
+                every: 1,
+                from: 0,
+                until: 3,
+                walkers: None,
+            },
+        );
+        assert_eq!(
+            csv,
+            "step,walker,location,path_position,goal\n\
+             0,AAA,AAA,0,false\n0,BBA,BBA,0,false\n\
+             1,AAA,AAB,1,false\n1,BBA,BBB,1,false\n\
+             2,AAA,AAZ,0,true\n2,BBA,BBC,0,false\n"
+        );
+    }
+
+    #[test]
+    fn sampled_json_lines() {
+        let jsonl = trace(
+            Format::JsonLines,
+            TraceOptions {
+                every: 2,
+                from: 1_000_000,
+                until: 1_000_007,
+                walkers: Some(Matcher::Exact("BBA".into())),
+            },
+        );
+        let records = jsonl
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        // after the first step BBA keeps cycling through BBB, BBC and BBZ
+        assert_eq!(records.len(), 4);
+        assert_eq!(
+            records[0],
+            serde_json::json!({
+                "step": 1_000_000,
+                "walker": "BBA",
+                "location": "BBB",
+                "path_position": 0,
+                "goal": false,
+            })
+        );
+        let steps = records
+            .iter()
+            .map(|r| r["step"].as_u64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![1_000_000, 1_000_002, 1_000_004, 1_000_006]);
+        assert_eq!(records[1]["location"], "BBZ");
+        assert_eq!(records[1]["goal"], true);
+    }
+
+    #[test]
+    fn formats_by_extension() {
+        for (file, format) in [
+            ("trace.csv", Format::Csv),
+            ("trace.jsonl", Format::JsonLines),
+            ("trace.json", Format::JsonLines),
+            ("trace", Format::Csv),
+        ] {
+            assert_eq!(Format::from_file_name(std::path::Path::new(file)), format);
+        }
+    }
+}
@@ -0,0 +1,6 @@
+// the solution lives in a library so the benchmarks in `benches/` can reach it
+mod day08;
+
+#[cfg(feature = "bench")]
+pub use day08::bench;
+pub use day08::main;
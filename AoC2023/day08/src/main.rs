@@ -1,5 +1,3 @@
-mod day08;
-
 use anyhow::Result;
 
 fn main() -> Result<()> {